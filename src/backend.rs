@@ -0,0 +1,87 @@
+// ---------- Arithmetic backends for the cycle detector ----------
+//
+// Everything the detector does to a number goes through `Backend`: parity,
+// right shifts, 3n+1, comparison, bit length and decimal formatting (Display).
+// Values enter and leave as BigUint, so the rest of the crate never sees which
+// backend ran. Implementations:
+//   Big       plain num-bigint, in place through its *Assign operators (default)
+//   LimbBuf   u64 limbs with 3n+1 and the shift fused into one pass
+//   Gmp       GNU MP through rug, with `--features gmp`
+// `Num` is the one the search uses, chosen at build time.
+
+use std::fmt::Display;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::maps::StepMap;
+
+pub trait Backend: Clone + Ord + Display + Send {
+    fn from_biguint(n: &BigUint) -> Self;
+    fn to_biguint(&self) -> BigUint;
+    /// Overwrite with another value, reusing the allocation where the backend can
+    fn copy_from(&mut self, other: &Self);
+
+    fn is_zero(&self) -> bool;
+    fn is_one(&self) -> bool;
+    fn is_odd(&self) -> bool;
+    fn bits(&self) -> u64;
+    /// Trailing zero bits; 0 for zero
+    fn trailing_zeros(&self) -> u64;
+
+    fn shr_assign(&mut self, s: u64);
+    /// x = 3x + 1
+    fn triple_plus_one(&mut self);
+
+    /// One step of `map` in place; returns the standard steps it covers
+    fn step(&mut self, map: StepMap) -> u64 {
+        let shift = |x: &mut Self| {
+            let s = x.trailing_zeros();
+            x.shr_assign(s);
+            s
+        };
+        match (map, self.is_odd()) {
+            (StepMap::Syracuse, true) => { self.triple_plus_one(); 1 + shift(self) }
+            (StepMap::Syracuse, false) => shift(self),
+            (StepMap::Shortcut, true) => { self.triple_plus_one(); self.shr_assign(1); 2 }
+            (StepMap::Standard, true) => { self.triple_plus_one(); 1 }
+            (_, false) => { self.shr_assign(1); 1 }
+        }
+    }
+}
+
+#[cfg(not(feature = "gmp"))]
+pub type Num = Big;
+#[cfg(feature = "gmp")]
+pub type Num = crate::gmp::Gmp;
+
+/// Name of the build's backend, for reports
+pub const NUM_NAME: &str = if cfg!(feature = "gmp") { "gmp" } else { "biguint" };
+
+/// num-bigint as a backend. A newtype so the trait's method names do not
+/// collide with num-traits' on BigUint itself.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Big(pub BigUint);
+
+impl Backend for Big {
+    fn from_biguint(n: &BigUint) -> Self { Big(n.clone()) }
+    fn to_biguint(&self) -> BigUint { self.0.clone() }
+    fn copy_from(&mut self, other: &Self) { self.0.clone_from(&other.0) }
+
+    fn is_zero(&self) -> bool { self.0.is_zero() }
+    fn is_one(&self) -> bool { self.0.is_one() }
+    fn is_odd(&self) -> bool { self.0.is_odd() }
+    fn bits(&self) -> u64 { self.0.bits() }
+    fn trailing_zeros(&self) -> u64 { self.0.trailing_zeros().unwrap_or(0) }
+
+    fn shr_assign(&mut self, s: u64) { self.0 >>= s }
+    fn triple_plus_one(&mut self) {
+        self.0 *= 3u32;
+        self.0 += 1u32;
+    }
+}
+
+impl Display for Big {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { self.0.fmt(f) }
+}
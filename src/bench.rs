@@ -0,0 +1,331 @@
+// ---------- Benchmarks for the core loop ----------
+//
+// `bench` times the hot paths at 64, 128, 512 and 2000 bits:
+//   step/<parity>/<bits>     one `collatz_next` on an odd or even value
+//   alloc/<map>/<bits>       one step of a map on BigUints, a new value per step
+//   inplace/<map>/<bits>     the same walk stepped in place on a `LimbBuf`
+//   classify/<mode>/<bits>   `detect_outcome` on consecutive or random starts
+//   floyd/<map>/<bits>       Floyd under the standard, shortcut or Syracuse map
+//   floyd/alloc/<bits>       the allocating standard Floyd, for comparison
+//   backend/<name>/<bits>    shortcut Floyd on each arithmetic backend in the build
+//   lanes/seq/<bits>         consecutive starts through the lockstep lane engine,
+//   scalar/seq/<bits>        and the same starts one at a time, at 32 and 63 bits
+//   rng/<bits>               `gen_range_biguint` over [2^(bits-1), 2^bits - 1]
+//   rng/<generator>          raw u64 output of each `--rng` generator
+//   rng/seek/<generator>     a jump to a far word and one draw, where the generator can
+//   render/<bits>            one offscreen viz frame of a start that size
+//   decimal/<bits>           the viz label (`short_decimal`) of a value that size,
+//   decimal/full/<bits>      and its full decimal conversion, at 2000 and 100000 bits
+// Each case is warmed up, then sampled several times; the median ns/op is
+// reported. Baselines are plain `name ns_per_op` lines so they diff well.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use num_bigint::BigUint;
+use num_traits::One;
+
+use crate::backend::{Backend, Big, NUM_NAME};
+use crate::lanes::LaneEngine;
+use crate::limbs::LimbBuf;
+use crate::maps::{StepMap, detect_outcome_in, detect_outcome_map};
+use crate::numexpr::parse_u64;
+use crate::rng::{RandomSource, Rng, RngKind};
+use crate::viz::OffscreenFrame;
+use crate::{collatz_next, detect_outcome, detect_outcome_with, short_decimal};
+
+type BoxError = Box<dyn std::error::Error>;
+
+const BIT_LENGTHS: [u64; 4] = [64, 128, 512, 2000];
+const SAMPLES: u32 = 5;
+// Inputs are fixed per case so runs on different builds measure the same work
+const SEED: u64 = 0x00C0_11A7_2BE4_C4ED;
+
+struct Case {
+    name: String,
+    // Runs one batch of operations, returning how many it did
+    run: Box<dyn FnMut() -> u64>,
+}
+
+fn case(name: String, run: impl FnMut() -> u64 + 'static) -> Case {
+    Case { name, run: Box::new(run) }
+}
+
+/// Random value with exactly `bits` bits, forced odd or even
+fn sample(rng: &mut Rng, bits: u64, odd: bool) -> BigUint {
+    let lo = BigUint::one() << (bits - 1);
+    let hi = (BigUint::one() << bits) - 1u32;
+    let mut n = rng.gen_range_biguint(&lo, &hi);
+    n.set_bit(0, odd);
+    n
+}
+
+fn backend_case<N: Backend>(out: &mut Vec<Case>, name: &str, bits: u64, starts: &[BigUint]) {
+    let (starts, mut i) = (starts.to_vec(), 0);
+    out.push(case(format!("backend/{name}/{bits}"), move || {
+        black_box(detect_outcome_in::<N>(&starts[i % starts.len()], StepMap::Shortcut));
+        i += 1;
+        1
+    }));
+}
+
+fn cases() -> Vec<Case> {
+    let mut rng = Rng::from_seed(SEED);
+    let mut out = Vec::new();
+    for bits in BIT_LENGTHS {
+        for (parity, odd) in [("odd", true), ("even", false)] {
+            let x = sample(&mut rng, bits, odd);
+            out.push(case(format!("step/{parity}/{bits}"), move || {
+                for _ in 0..1000 { black_box(collatz_next(black_box(&x))); }
+                1000
+            }));
+        }
+
+        // 1000 steps along one orbit, starting over whenever it reaches 1
+        let start = sample(&mut rng, bits, true);
+        for map in StepMap::ALL {
+            let (s, mut x) = (start.clone(), start.clone());
+            out.push(case(format!("alloc/{}/{bits}", map.name()), move || {
+                for _ in 0..1000 {
+                    if x.is_one() { x = s.clone(); }
+                    x = map.step(black_box(&x)).0;
+                }
+                1000
+            }));
+            let (s, mut buf) = (start.clone(), LimbBuf::from_biguint(&start));
+            out.push(case(format!("inplace/{}/{bits}", map.name()), move || {
+                for _ in 0..1000 {
+                    if buf.is_one() { buf.assign(&s); }
+                    black_box(buf.step(map));
+                }
+                1000
+            }));
+        }
+
+        let first = BigUint::one() << (bits - 1);
+        let mut next = first.clone();
+        out.push(case(format!("classify/seq/{bits}"), move || {
+            black_box(detect_outcome(&next));
+            next += 1u32;
+            1
+        }));
+        let starts: Vec<BigUint> = (0..64).map(|_| sample(&mut rng, bits, true)).collect();
+        for map in StepMap::ALL {
+            let (starts, mut i) = (starts.clone(), 0);
+            out.push(case(format!("floyd/{}/{bits}", map.name()), move || {
+                black_box(detect_outcome_map(&starts[i % starts.len()], map));
+                i += 1;
+                1
+            }));
+        }
+        let (alloc_starts, mut i) = (starts.clone(), 0);
+        out.push(case(format!("floyd/alloc/{bits}"), move || {
+            black_box(detect_outcome_with(&alloc_starts[i % alloc_starts.len()], collatz_next));
+            i += 1;
+            1
+        }));
+        backend_case::<Big>(&mut out, "biguint", bits, &starts);
+        backend_case::<LimbBuf>(&mut out, "limbs", bits, &starts);
+        #[cfg(feature = "gmp")]
+        backend_case::<crate::gmp::Gmp>(&mut out, "gmp", bits, &starts);
+        let mut i = 0;
+        out.push(case(format!("classify/random/{bits}"), move || {
+            black_box(detect_outcome(&starts[i % starts.len()]));
+            i += 1;
+            1
+        }));
+
+        let lo = BigUint::one() << (bits - 1);
+        let hi = (BigUint::one() << bits) - 1u32;
+        let mut r = Rng::from_seed(SEED ^ bits);
+        out.push(case(format!("rng/{bits}"), move || {
+            for _ in 0..100 { black_box(r.gen_range_biguint(&lo, &hi)); }
+            100
+        }));
+
+        let mut frame = OffscreenFrame::new(sample(&mut rng, bits, true), 10_000);
+        out.push(case(format!("render/{bits}"), move || {
+            black_box(frame.draw());
+            1
+        }));
+    }
+    for kind in RngKind::ALL {
+        let mut r = Rng::new(kind, SEED);
+        out.push(case(format!("rng/{}", kind.name()), move || {
+            for _ in 0..1000 { black_box(r.next_u64()); }
+            1000
+        }));
+        // Generators that can jump: a far seek, then one word
+        let mut r = Rng::new(kind, SEED);
+        if r.seek(0).is_ok() {
+            let mut i = 0u64;
+            out.push(case(format!("rng/seek/{}", kind.name()), move || {
+                i = i.wrapping_add(0x1_0000_0001);
+                r.seek(i).unwrap();
+                black_box(r.next_u64());
+                1
+            }));
+        }
+    }
+    for bits in [2000u64, 100_000] {
+        let n = sample(&mut rng, bits, true);
+        let full = n.clone();
+        out.push(case(format!("decimal/{bits}"), move || {
+            black_box(short_decimal(&n, 12, 12));
+            1
+        }));
+        out.push(case(format!("decimal/full/{bits}"), move || {
+            black_box(full.to_str_radix(10));
+            1
+        }));
+    }
+    for bits in [32u32, 63] {
+        let first = 1u64 << bits;
+        let mut engine = LaneEngine::new(first, u64::MAX, 64 << 20);
+        out.push(case(format!("lanes/seq/{bits}"), move || {
+            for _ in 0..100 { black_box(engine.next_result()); }
+            100
+        }));
+        let mut next = first;
+        out.push(case(format!("scalar/seq/{bits}"), move || {
+            black_box(detect_outcome(&BigUint::from(next)));
+            next += 1;
+            1
+        }));
+    }
+    out
+}
+
+/// Median ns/op over SAMPLES timed windows of `budget / SAMPLES` each
+fn measure(c: &mut Case, budget: Duration) -> f64 {
+    let window = budget / SAMPLES;
+    // Warm-up: caches, allocator, branch predictors
+    let warm = Instant::now();
+    while warm.elapsed() < window / 4 { (c.run)(); }
+    let mut per_op: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let t = Instant::now();
+            let mut ops = 0u64;
+            while t.elapsed() < window { ops += (c.run)(); }
+            t.elapsed().as_nanos() as f64 / ops as f64
+        })
+        .collect();
+    per_op.sort_by(f64::total_cmp);
+    per_op[per_op.len() / 2]
+}
+
+fn load_baseline(path: &str) -> Result<BTreeMap<String, f64>, BoxError> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut map = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') { continue; }
+        let mut parts = line.split_whitespace();
+        let (Some(name), Some(ns)) = (parts.next(), parts.next()) else {
+            return Err(format!("{path}:{}: expected '<name> <ns_per_op>'", i + 1).into());
+        };
+        map.insert(name.to_string(), ns.parse::<f64>().map_err(|_| format!("{path}:{}: bad number {ns}", i + 1))?);
+    }
+    Ok(map)
+}
+
+fn human(ns: f64) -> String {
+    match ns {
+        n if n >= 1e9 => format!("{:.2} s", n / 1e9),
+        n if n >= 1e6 => format!("{:.2} ms", n / 1e6),
+        n if n >= 1e3 => format!("{:.2} us", n / 1e3),
+        n => format!("{n:.1} ns"),
+    }
+}
+
+/// `bench [--filter SUBSTR] [--time-ms N] [--save PATH] [--baseline PATH] [--max-regress PCT]`
+pub fn bench_main(mut args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let mut filter: Option<String> = None;
+    let mut budget = Duration::from_millis(500);
+    let mut save: Option<String> = None;
+    let mut baseline: Option<String> = None;
+    let mut max_regress: Option<f64> = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--filter" => filter = Some(value()?),
+            "--time-ms" => budget = Duration::from_millis(parse_u64(&value()?).map_err(|e| format!("--time-ms: {e}"))?.max(10)),
+            "--save" => save = Some(value()?),
+            "--baseline" => baseline = Some(value()?),
+            "--max-regress" => max_regress = Some(value()?.parse().map_err(|_| "invalid --max-regress (percent)")?),
+            other => return Err(format!("unknown bench option: {other}").into()),
+        }
+    }
+    if cfg!(debug_assertions) {
+        eprintln!("warning: this is a debug build; use `cargo run --release -- bench` for meaningful numbers");
+    }
+    eprintln!("search backend: {NUM_NAME}");
+    let base = baseline.as_deref().map(load_baseline).transpose()?;
+
+    let mut results: Vec<(String, f64)> = Vec::new();
+    let mut regressions = 0;
+    for mut c in cases() {
+        if filter.as_ref().is_some_and(|f| !c.name.contains(f.as_str())) { continue; }
+        let ns = measure(&mut c, budget);
+        let cmp = match base.as_ref().and_then(|b| b.get(&c.name)) {
+            Some(&old) => {
+                let pct = (ns - old) / old * 100.0;
+                let flag = if max_regress.is_some_and(|m| pct > m) { regressions += 1; "  REGRESSION" } else { "" };
+                format!("  {pct:+6.1}% vs {}{flag}", human(old))
+            }
+            None if base.is_some() => "  (new)".to_string(),
+            None => String::new(),
+        };
+        println!("{:<22} {:>12}/op{cmp}", c.name, human(ns));
+        results.push((c.name, ns));
+    }
+
+    if let Some(path) = save {
+        let mut text = String::from("# collatz bench baseline: <case> <ns_per_op>\n");
+        for (name, ns) in &results { text.push_str(&format!("{name} {ns:.1}\n")); }
+        fs::write(&path, text).map_err(|e| format!("{path}: {e}"))?;
+        eprintln!("baseline saved to {path}");
+    }
+    if regressions > 0 {
+        return Err(format!("{regressions} case(s) slower than the baseline by more than {}%", max_regress.unwrap_or_default()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baselines_read_back_what_save_writes() {
+        let path = std::env::temp_dir().join(format!("collatz-bench-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "# collatz bench baseline: <case> <ns_per_op>\nstep/odd/64 41.5\n\nrng/2000 1234.0\n").unwrap();
+        let base = load_baseline(path).unwrap();
+        assert_eq!(base.into_iter().collect::<Vec<_>>(), [("rng/2000".to_string(), 1234.0), ("step/odd/64".to_string(), 41.5)]);
+        fs::write(path, "step/odd/64 41.5\nrender/64\n").unwrap();
+        assert_eq!(load_baseline(path).unwrap_err().to_string(), format!("{path}:2: expected '<name> <ns_per_op>'"));
+        fs::write(path, "step/odd/64 fast\n").unwrap();
+        assert_eq!(load_baseline(path).unwrap_err().to_string(), format!("{path}:1: bad number fast"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn case_names_are_distinct_and_cover_every_bit_length() {
+        let names: Vec<String> = cases().into_iter().map(|c| c.name).collect();
+        assert_eq!(names.iter().collect::<std::collections::BTreeSet<_>>().len(), names.len());
+        for bits in BIT_LENGTHS {
+            assert!(names.contains(&format!("classify/random/{bits}")), "{names:?}");
+        }
+    }
+
+    #[test]
+    fn measure_reports_time_per_operation() {
+        let mut c = case("sleep".into(), || { std::thread::sleep(Duration::from_millis(1)); 2 });
+        let ns = measure(&mut c, Duration::from_millis(50));
+        // Two operations per millisecond-long batch
+        assert!((4e5..5e6).contains(&ns), "{ns}");
+        assert_eq!((human(12.0), human(1.5e3), human(2.5e6), human(3e9)), ("12.0 ns".into(), "1.50 us".into(), "2.50 ms".into(), "3.00 s".into()));
+    }
+}
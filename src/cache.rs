@@ -0,0 +1,148 @@
+// ---------- Memoized stopping times for sequential scans of small ranges ----------
+//
+// Below ~2^40 neighbouring starts share most of their orbits, so a walk can stop
+// at the first value whose remaining stopping time and peak are already known.
+// Values below `dense_len` live in flat arrays indexed by value; anything larger
+// goes into a hash map until the memory cap is reached, after which the map
+// simply stops growing. Only orbits whose peak fits in a u128 are cached, so a
+// hit always rebuilds the exact same Classified as `detect_outcome`.
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive};
+
+use crate::{Classified, Outcome, collatz_next, detect_outcome};
+
+const EMPTY: u32 = u32::MAX;
+// Per-entry cost: u32 steps + u128 peak in the dense arrays; the map pays for
+// the boxed BigUint key and table overhead on top
+const DENSE_ENTRY_BYTES: u64 = 4 + 16;
+const MAP_ENTRY_BYTES: u64 = 96;
+// A walk this long without a hit or a 1 is handed to Floyd, which also
+// copes with orbits that never reach 1
+const MAX_WALK: usize = 1 << 20;
+
+pub struct OrbitCache {
+    steps: Vec<u32>,  // remaining steps to 1 from value i, EMPTY if unknown
+    peaks: Vec<u128>, // largest value on the orbit from i onwards
+    map: HashMap<BigUint, (u32, u128)>,
+    map_cap: usize,
+    lookups: u64,
+    hits: u64,
+    walked: u64, // steps actually computed
+    path: Vec<BigUint>, // reused across walks
+}
+
+impl OrbitCache {
+    /// Split `mem_bytes` between the dense arrays (up to `dense_limit` values) and the map
+    pub fn new(mem_bytes: u64, dense_limit: u64) -> Self {
+        let dense_len = dense_limit.min(mem_bytes * 3 / 4 / DENSE_ENTRY_BYTES) as usize;
+        let map_cap = ((mem_bytes - dense_len as u64 * DENSE_ENTRY_BYTES) / MAP_ENTRY_BYTES) as usize;
+        let mut steps = vec![EMPTY; dense_len];
+        if dense_len > 1 { steps[1] = 0; }
+        let mut peaks = vec![0u128; dense_len];
+        if dense_len > 1 { peaks[1] = 1; }
+        OrbitCache { steps, peaks, map: HashMap::new(), map_cap, lookups: 0, hits: 0, walked: 0, path: Vec::new() }
+    }
+
+    fn get(&self, x: &BigUint) -> Option<(u32, u128)> {
+        if let Some(i) = x.to_usize() && i < self.steps.len() {
+            let s = self.steps[i];
+            return (s != EMPTY).then(|| (s, self.peaks[i]));
+        }
+        self.map.get(x).copied()
+    }
+
+    fn put(&mut self, x: &BigUint, steps: u32, peak: u128) {
+        if let Some(i) = x.to_usize() && i < self.steps.len() {
+            self.steps[i] = steps;
+            self.peaks[i] = peak;
+        } else if self.map.len() < self.map_cap {
+            self.map.insert(x.clone(), (steps, peak));
+        }
+    }
+
+    /// Same result as `detect_outcome`, reusing whatever earlier walks left behind
+    pub fn classify(&mut self, start: &BigUint) -> Classified {
+        self.path.clear();
+        let mut x = start.clone();
+        self.lookups += 1;
+        let (tail_steps, tail_peak) = loop {
+            if let Some(hit) = self.get(&x) {
+                // Running into the seeded 1 is just the end of the orbit, not a hit
+                if hit.0 > 0 { self.hits += 1; }
+                break hit;
+            }
+            if x.is_one() { break (0, 1); }
+            if self.path.len() >= MAX_WALK { return detect_outcome(start); }
+            let next = collatz_next(&x);
+            self.path.push(x);
+            x = next;
+        };
+
+        // Walk the path backwards filling in remaining steps and suffix peaks
+        let mut steps = tail_steps as u64;
+        let mut peak = tail_peak;
+        let mut overflow = false;
+        let path = std::mem::take(&mut self.path);
+        for v in path.iter().rev() {
+            steps += 1;
+            match v.to_u128() {
+                Some(v) if !overflow => peak = peak.max(v),
+                _ => overflow = true,
+            }
+            if !overflow && steps < EMPTY as u64 { self.put(v, steps as u32, peak); }
+        }
+        self.walked += path.len() as u64;
+        self.path = path;
+
+        if overflow {
+            // Peak is beyond u128; nothing above was cached, so recompute exactly
+            return detect_outcome(start);
+        }
+        Classified { outcome: Outcome::ReachesOne, stopping_time: Some(steps), peak: BigUint::from(peak) }
+    }
+
+    /// One-line hit-rate report for the end-of-run summary
+    pub fn report(&self) -> String {
+        let bytes = self.steps.len() as u64 * DENSE_ENTRY_BYTES + self.map.len() as u64 * MAP_ENTRY_BYTES;
+        let n = self.lookups.max(1) as f64;
+        format!(
+            "orbit cache: {}/{} starts ended on a cached value ({:.1}%), {:.1} steps walked per start, {} map entries, ~{} MiB",
+            self.hits, self.lookups, 100.0 * self.hits as f64 / n, self.walked as f64 / n, self.map.len(), bytes >> 20
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_results_match_floyd() {
+        // Dense slots for the first 1000 values, a map for the rest, then a cap that stops it growing
+        let walked: Vec<u64> = [1 << 20, 4096]
+            .into_iter()
+            .map(|mem| {
+                let mut cache = OrbitCache::new(mem, 1000);
+                for n in (1..3000u32).chain((1..3000).rev().step_by(7)) {
+                    let n = BigUint::from(n);
+                    assert_eq!(cache.classify(&n), detect_outcome(&n), "start {n} with {mem} bytes");
+                }
+                assert!(cache.hits > 0, "{}", cache.report());
+                cache.walked
+            })
+            .collect();
+        assert!(walked[0] < walked[1], "a smaller cache walks more: {walked:?}");
+    }
+
+    #[test]
+    fn peaks_beyond_u128_fall_back_to_floyd() {
+        let mut cache = OrbitCache::new(1 << 20, 1000);
+        let start = (BigUint::one() << 140u32) - 1u32;
+        // Twice: the second walk ends on the tail the first one cached below the peak
+        for _ in 0..2 { assert_eq!(cache.classify(&start), detect_outcome(&start)); }
+        assert_eq!(cache.classify(&BigUint::from(27u32)).stopping_time, Some(111));
+    }
+}
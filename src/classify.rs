@@ -0,0 +1,303 @@
+// ---------- Batch classification of starts from a file or stdin ----------
+//
+// `classify` reads one start per line (any numeric expression), classifies
+// each with Floyd under the chosen map plus a direct walk for the orbit statistics, and
+// writes one JSON or CSV row per input line in input order. Worker threads
+// pull lines as they are read, and each row is written as soon as every row
+// before it is out, so a pipe sees results while its input is still open.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::json::quote;
+use crate::numexpr::{parse_biguint, parse_u64};
+use crate::maps::{StepMap, detect_outcome_map};
+use crate::{Outcome, collatz_next};
+
+type BoxError = Box<dyn std::error::Error>;
+
+// Lines read ahead of the last written row; bounds memory behind one slow orbit
+const IN_FLIGHT: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format { Json, Csv }
+
+const CSV_HEADER: &str = "input,start,bits,outcome,total_steps,odd_steps,even_steps,glide,peak_step,peak_bits,peak,error";
+
+// Everything we report about one orbit that reaches 1
+struct OrbitStats {
+    odd_steps: u64,
+    even_steps: u64,
+    glide: u64,     // steps until the orbit first drops below the start
+    peak_step: u64, // index of the first occurrence of the peak
+}
+
+/// Walk an orbit already known to reach 1
+fn orbit_stats(start: &BigUint, peak: &BigUint) -> OrbitStats {
+    let mut st = OrbitStats { odd_steps: 0, even_steps: 0, glide: 0, peak_step: 0 };
+    let mut x = start.clone();
+    let mut steps = 0u64;
+    let mut glide = None;
+    while !x.is_one() {
+        if x.is_even() { st.even_steps += 1 } else { st.odd_steps += 1 }
+        x = collatz_next(&x);
+        steps += 1;
+        if glide.is_none() && x < *start { glide = Some(steps); }
+        if st.peak_step == 0 && x == *peak { st.peak_step = steps; }
+    }
+    // 1 never drops below itself; report its glide as 0 like the other fields
+    st.glide = glide.unwrap_or(0);
+    st
+}
+
+fn outcome_name(o: Outcome) -> &'static str {
+    match o {
+        Outcome::ReachesOne => "reaches_one",
+        Outcome::NontrivialCycle => "nontrivial_cycle",
+        Outcome::StepsOverflow => "steps_overflow",
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+/// Classify one input line into a finished output row
+fn classify_line(line: &str, format: Format, map: StepMap) -> String {
+    let input = line.trim();
+    let start = match parse_biguint(input) {
+        Ok(n) if !n.is_zero() => n,
+        Ok(_) => return error_row(input, "0 has no Collatz orbit", format),
+        Err(e) => return error_row(input, &e, format),
+    };
+    let c = detect_outcome_map(&start, map);
+    let stats = (c.outcome == Outcome::ReachesOne).then(|| orbit_stats(&start, &c.peak));
+    let opt = |v: Option<u64>| v.map(|v| v.to_string());
+    let fields = [
+        opt(c.stopping_time),
+        opt(stats.as_ref().map(|s| s.odd_steps)),
+        opt(stats.as_ref().map(|s| s.even_steps)),
+        opt(stats.as_ref().map(|s| s.glide)),
+        opt(stats.as_ref().map(|s| s.peak_step)),
+    ];
+    match format {
+        Format::Json => {
+            let f: Vec<String> = fields.iter().map(|v| v.clone().unwrap_or_else(|| "null".into())).collect();
+            format!(
+                "{{\"input\":{},\"start\":\"{start}\",\"bits\":{},\"outcome\":\"{}\",\"total_steps\":{},\"odd_steps\":{},\"even_steps\":{},\"glide\":{},\"peak_step\":{},\"peak_bits\":{},\"peak\":\"{}\",\"error\":null}}",
+                quote(input), start.bits(), outcome_name(c.outcome), f[0], f[1], f[2], f[3], f[4], c.peak.bits(), c.peak
+            )
+        }
+        Format::Csv => {
+            let f: Vec<String> = fields.iter().map(|v| v.clone().unwrap_or_default()).collect();
+            format!(
+                "{},{start},{},{},{},{},{},{},{},{},{},",
+                csv_field(input), start.bits(), outcome_name(c.outcome), f[0], f[1], f[2], f[3], f[4], c.peak.bits(), c.peak
+            )
+        }
+    }
+}
+
+fn error_row(input: &str, err: &str, format: Format) -> String {
+    match format {
+        Format::Json => format!(
+            "{{\"input\":{},\"start\":null,\"bits\":null,\"outcome\":null,\"total_steps\":null,\"odd_steps\":null,\"even_steps\":null,\"glide\":null,\"peak_step\":null,\"peak_bits\":null,\"peak\":null,\"error\":{}}}",
+            quote(input), quote(err)
+        ),
+        Format::Csv => format!("{},,,,,,,,,,,{}", csv_field(input), csv_field(err)),
+    }
+}
+
+/// Classify every line of `reader` on `threads` threads, writing rows to `out` in input order
+fn classify_stream(reader: impl BufRead + Send, out: &mut dyn Write, format: Format, map: StepMap, threads: usize) -> Result<(), BoxError> {
+    let (line_tx, line_rx) = mpsc::sync_channel::<(u64, String)>(threads);
+    let line_rx = Mutex::new(line_rx);
+    let (row_tx, row_rx) = mpsc::channel::<(u64, String)>();
+    // The reader takes a permit per line and the writer hands one back per row
+    let (permit_tx, permit_rx) = mpsc::sync_channel::<()>(IN_FLIGHT);
+    for _ in 0..IN_FLIGHT { permit_tx.send(())?; }
+    thread::scope(|s| {
+        let read = s.spawn(move || -> io::Result<()> {
+            let mut seq = 0;
+            for line in reader.lines() {
+                let line = line?;
+                // Blank lines and # comments are not inputs
+                let t = line.trim();
+                if t.is_empty() || t.starts_with('#') { continue; }
+                if permit_rx.recv().is_err() || line_tx.send((seq, line)).is_err() { break; }
+                seq += 1;
+            }
+            Ok(())
+        });
+        for _ in 0..threads {
+            let (line_rx, row_tx) = (&line_rx, row_tx.clone());
+            s.spawn(move || {
+                loop {
+                    let next = line_rx.lock().expect("classify line queue poisoned").recv();
+                    let Ok((seq, line)) = next else { break };
+                    if row_tx.send((seq, classify_line(&line, format, map))).is_err() { break; }
+                }
+            });
+        }
+        drop(row_tx);
+        // Dropping the channels on a write error lets the reader and workers wind down
+        write_in_order(row_rx, permit_tx, out)?;
+        read.join().expect("classify reader panicked")?;
+        Ok(())
+    })
+}
+
+/// Write rows as their turn comes, flushing whenever no further row is ready
+fn write_in_order(rows: Receiver<(u64, String)>, permits: SyncSender<()>, out: &mut dyn Write) -> io::Result<()> {
+    let mut waiting = BTreeMap::new();
+    let mut next = 0u64;
+    loop {
+        let (seq, row) = match rows.try_recv() {
+            Ok(r) => r,
+            Err(TryRecvError::Empty) => {
+                out.flush()?;
+                match rows.recv() {
+                    Ok(r) => r,
+                    Err(_) => break,
+                }
+            }
+            Err(TryRecvError::Disconnected) => break,
+        };
+        waiting.insert(seq, row);
+        while let Some(row) = waiting.remove(&next) {
+            writeln!(out, "{row}")?;
+            next += 1;
+            let _ = permits.send(());
+        }
+    }
+    out.flush()
+}
+
+/// `classify [--input PATH|-] [--output PATH] [--format json|csv] [--map M] [--threads N]`
+pub fn classify_main(mut args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let mut input = String::from("-");
+    let mut output: Option<String> = None;
+    let mut format = Format::Json;
+    let mut map = StepMap::default();
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--input" | "-i" => input = value()?,
+            "--output" | "-o" => output = Some(value()?),
+            "--format" => {
+                format = match value()?.as_str() {
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    other => return Err(format!("unknown --format {other} (expected json or csv)").into()),
+                }
+            }
+            "--map" => {
+                let v = value()?;
+                map = StepMap::parse(&v).ok_or_else(|| format!("unknown --map {v} (expected standard, shortcut or syracuse)"))?;
+            }
+            "--threads" => threads = (parse_u64(&value()?).map_err(|e| format!("--threads: {e}"))? as usize).max(1),
+            other => return Err(format!("unknown classify option: {other}").into()),
+        }
+    }
+
+    let reader: Box<dyn BufRead + Send> = if input == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(&input).map_err(|e| format!("{input}: {e}"))?))
+    };
+    let mut out: Box<dyn Write> = match output {
+        Some(ref p) => Box::new(BufWriter::new(File::create(p).map_err(|e| format!("{p}: {e}"))?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    if format == Format::Csv { writeln!(out, "{CSV_HEADER}")?; }
+
+    classify_stream(reader, &mut out, format, map, threads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(input: &str, format: Format, map: StepMap, threads: usize) -> Vec<String> {
+        let mut out = Vec::new();
+        classify_stream(input.as_bytes(), &mut out, format, map, threads).unwrap();
+        String::from_utf8(out).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn rows_carry_the_orbit_statistics_of_27() {
+        // Counted in standard steps whichever map does the classifying
+        for map in StepMap::ALL {
+            assert_eq!(rows("27\nx\n\n# skipped\n0\n", Format::Csv, map, 2), [
+                "27,27,5,reaches_one,111,41,70,96,77,14,9232,",
+                "x,,,,,,,,,,,\"expected a number at column 1, found 'x' in 'x'\"",
+                "0,,,,,,,,,,,0 has no Collatz orbit",
+            ], "{map:?}");
+        }
+        let json = &rows("27\n", Format::Json, StepMap::default(), 1)[0];
+        assert!(json.contains("\"total_steps\":111,\"odd_steps\":41,\"even_steps\":70,\"glide\":96,\"peak_step\":77"), "{json}");
+    }
+
+    #[test]
+    fn rows_come_back_in_input_order() {
+        // More lines than may be in flight, so the permits have to come back
+        let lines: Vec<String> = (1..=2 * IN_FLIGHT as u64 + 7).rev().map(|n| n.to_string()).collect();
+        let rows = rows(&lines.join("\n"), Format::Csv, StepMap::default(), 4);
+        let inputs: Vec<&str> = rows.iter().map(|r| r.split(',').next().unwrap()).collect();
+        assert_eq!(inputs, lines);
+    }
+
+    // Stdin stand-in that blocks until the test sends the next piece
+    struct Pipe(Receiver<Vec<u8>>, Vec<u8>);
+
+    impl io::Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.1.is_empty() { self.1 = self.0.recv().unwrap_or_default(); }
+            let n = buf.len().min(self.1.len());
+            buf[..n].copy_from_slice(&self.1[..n]);
+            self.1.drain(..n);
+            Ok(n)
+        }
+    }
+
+    // Hands each flushed batch of output to the test
+    struct Flushed(Vec<u8>, mpsc::Sender<String>);
+
+    impl Write for Flushed {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            if !self.0.is_empty() { let _ = self.1.send(String::from_utf8(std::mem::take(&mut self.0)).unwrap()); }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_row_is_written_before_the_input_ends() {
+        let (in_tx, in_rx) = mpsc::channel();
+        let (out_tx, out_rx) = mpsc::channel();
+        let run = thread::spawn(move || {
+            let mut out = Flushed(Vec::new(), out_tx);
+            classify_stream(BufReader::new(Pipe(in_rx, Vec::new())), &mut out, Format::Csv, StepMap::default(), 2).unwrap();
+        });
+        in_tx.send(b"27\n".to_vec()).unwrap();
+        let first = out_rx.recv_timeout(std::time::Duration::from_secs(10)).expect("no row while the input is open");
+        assert!(first.starts_with("27,27,5,reaches_one,111,"), "{first}");
+        in_tx.send(b"6\n".to_vec()).unwrap();
+        drop(in_tx);
+        run.join().unwrap();
+        let rest: String = out_rx.iter().collect();
+        assert!(rest.starts_with("6,6,3,reaches_one,8,"), "{rest}");
+    }
+}
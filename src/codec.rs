@@ -0,0 +1,349 @@
+// ---------- Parity-bit orbit codec and indexed orbit library files ----------
+//
+// An orbit is fully determined by its start and its parity sequence (bit i set
+// when step i applies 3x+1), so that is all we store: one bit per step instead
+// of every BigUint. Record layout (also the body of `orbit --format bin`):
+//
+//   varint   byte length of start, then the start (little-endian)
+//   varint   steps
+//   u8       flags (bit 0: truncated before reaching 1)
+//   bytes    ceil(steps / 8) of parity bits, step i at bit i % 8 of byte i / 8
+//
+// A library file holds many records with an index at the end for random access:
+//
+//   "CLZLIB1\n" | record 0 | record 1 | ... | u64 LE offset per record
+//   | u64 LE record count | u64 LE index offset | "CLZIDX1\n"
+//
+// Varints are unsigned LEB128.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::collatz_next;
+
+pub const LIBRARY_MAGIC: &[u8; 8] = b"CLZLIB1\n";
+const INDEX_MAGIC: &[u8; 8] = b"CLZIDX1\n";
+const FOOTER_LEN: u64 = 24;
+
+/// Unsigned LEB128
+pub fn write_varint(out: &mut impl Write, mut v: u64) -> io::Result<()> {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 { return out.write_all(&[byte]); }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+pub fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut b = [0u8];
+        r.read_exact(&mut b)?;
+        v |= ((b[0] & 0x7f) as u64) << shift;
+        if b[0] & 0x80 == 0 { return Ok(v); }
+    }
+    Err(invalid("varint longer than 64 bits"))
+}
+
+fn invalid(why: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, why.to_string()) }
+
+/// Start plus parity string; everything else about the orbit is replayed from these
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedOrbit {
+    pub start: BigUint,
+    pub steps: u64,
+    pub reached_one: bool,
+    parity: Vec<u8>,
+}
+
+impl EncodedOrbit {
+    /// Walk `start` until it reaches 1 or `max_steps` steps have been taken
+    pub fn encode(start: &BigUint, max_steps: u64) -> Self {
+        let mut parity = Vec::new();
+        let mut x = start.clone();
+        let mut steps = 0u64;
+        while !x.is_one() && steps < max_steps {
+            let i = steps as usize;
+            if i.is_multiple_of(8) { parity.push(0); }
+            if x.is_odd() { parity[i / 8] |= 1 << (i % 8); }
+            x = collatz_next(&x);
+            steps += 1;
+        }
+        EncodedOrbit { start: start.clone(), steps, reached_one: x.is_one(), parity }
+    }
+
+    /// Parity of the value at step `i` (true = odd)
+    pub fn parity(&self, i: u64) -> bool {
+        self.parity[(i / 8) as usize] >> (i % 8) & 1 == 1
+    }
+
+    /// Values from the start through the last step, rebuilt from the parity bits.
+    /// Each item is checked against its stored parity, so a corrupt record shows up
+    /// as an error instead of a silently wrong orbit.
+    pub fn replay(&self) -> Replay<'_> {
+        Replay { orbit: self, x: Some(self.start.clone()), step: 0 }
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let bytes = self.start.to_bytes_le();
+        write_varint(out, bytes.len() as u64)?;
+        out.write_all(&bytes)?;
+        write_varint(out, self.steps)?;
+        out.write_all(&[u8::from(!self.reached_one)])?;
+        out.write_all(&self.parity)
+    }
+
+    /// Read one record that must fit in the next `limit` bytes (the rest of the
+    /// file or buffer). Lengths that run past it are rejected before anything is
+    /// allocated, so a corrupt varint cannot ask for gigabytes.
+    pub fn read_from(r: &mut impl Read, limit: u64) -> io::Result<Self> {
+        let mut r = r.take(limit);
+        let len = read_varint(&mut r)?;
+        if len > r.limit() { return Err(invalid("start length runs past the end of the record")); }
+        let mut bytes = vec![0u8; len as usize];
+        r.read_exact(&mut bytes)?;
+        let steps = read_varint(&mut r)?;
+        let mut flags = [0u8];
+        r.read_exact(&mut flags)?;
+        let parity_len = steps.div_ceil(8);
+        if parity_len > r.limit() { return Err(invalid("step count runs past the end of the record")); }
+        let mut parity = vec![0u8; parity_len as usize];
+        r.read_exact(&mut parity)?;
+        Ok(EncodedOrbit { start: BigUint::from_bytes_le(&bytes), steps, reached_one: flags[0] & 1 == 0, parity })
+    }
+}
+
+pub struct Replay<'a> {
+    orbit: &'a EncodedOrbit,
+    x: Option<BigUint>,
+    step: u64,
+}
+
+impl Iterator for Replay<'_> {
+    type Item = Result<BigUint, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.x.take()?;
+        if self.step < self.orbit.steps {
+            let odd = self.orbit.parity(self.step);
+            if odd != x.is_odd() {
+                return Some(Err(format!("parity bit {} does not match the replayed value", self.step)));
+            }
+            self.x = Some(if odd { &x * 3u32 + 1u32 } else { &x >> 1 });
+            self.step += 1;
+        }
+        Some(Ok(x))
+    }
+}
+
+// ---------- Library container ----------
+
+pub struct LibraryWriter {
+    out: BufWriter<File>,
+    pos: u64,
+    offsets: Vec<u64>,
+}
+
+impl LibraryWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(LIBRARY_MAGIC)?;
+        Ok(LibraryWriter { out, pos: LIBRARY_MAGIC.len() as u64, offsets: Vec::new() })
+    }
+
+    /// Append a record; returns its index
+    pub fn push(&mut self, orbit: &EncodedOrbit) -> io::Result<u64> {
+        let mut buf = Vec::new();
+        orbit.write_to(&mut buf)?;
+        self.out.write_all(&buf)?;
+        self.offsets.push(self.pos);
+        self.pos += buf.len() as u64;
+        Ok(self.offsets.len() as u64 - 1)
+    }
+
+    /// Write the index and footer; without this the file cannot be opened
+    pub fn finish(mut self) -> io::Result<()> {
+        let index_at = self.pos;
+        for off in &self.offsets {
+            self.out.write_all(&off.to_le_bytes())?;
+        }
+        self.out.write_all(&(self.offsets.len() as u64).to_le_bytes())?;
+        self.out.write_all(&index_at.to_le_bytes())?;
+        self.out.write_all(INDEX_MAGIC)?;
+        self.out.flush()
+    }
+}
+
+pub struct LibraryReader {
+    file: BufReader<File>,
+    offsets: Vec<u64>,
+    records_end: u64, // where the index starts
+}
+
+impl LibraryReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic).map_err(|_| invalid("not an orbit library"))?;
+        if &magic != LIBRARY_MAGIC { return Err(invalid("not an orbit library")); }
+
+        let len = file.seek(SeekFrom::End(0))?;
+        if len < LIBRARY_MAGIC.len() as u64 + FOOTER_LEN { return Err(invalid("orbit library has no index (unfinished write?)")); }
+        file.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        file.read_exact(&mut footer)?;
+        if &footer[16..] != INDEX_MAGIC { return Err(invalid("orbit library has no index (unfinished write?)")); }
+        let count = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let index_at = u64::from_le_bytes(footer[8..16].try_into().unwrap());
+        // The index fills exactly the space between the records and the footer
+        let index_len = count.checked_mul(8).ok_or_else(|| invalid("orbit library index is inconsistent"))?;
+        if index_at < LIBRARY_MAGIC.len() as u64 || index_at.checked_add(index_len) != Some(len - FOOTER_LEN) {
+            return Err(invalid("orbit library index is inconsistent"));
+        }
+
+        file.seek(SeekFrom::Start(index_at))?;
+        let mut raw = vec![0u8; index_len as usize];
+        file.read_exact(&mut raw)?;
+        let offsets = raw.chunks_exact(8).map(|c| u64::from_le_bytes(c.try_into().unwrap())).collect();
+        Ok(LibraryReader { file, offsets, records_end: index_at })
+    }
+
+    pub fn len(&self) -> u64 { self.offsets.len() as u64 }
+
+    /// Record `index`, read with a single seek
+    pub fn get(&mut self, index: u64) -> io::Result<EncodedOrbit> {
+        let off = *self.offsets.get(index as usize).ok_or_else(|| invalid("index out of range"))?;
+        if off < LIBRARY_MAGIC.len() as u64 || off >= self.records_end { return Err(invalid("record offset out of range")); }
+        self.file.seek(SeekFrom::Start(off))?;
+        EncodedOrbit::read_from(&mut self.file, self.records_end - off)
+    }
+}
+
+/// Read starts (one expression per line) into a new library file
+pub fn build_library(input: impl BufRead, path: &Path, max_steps: u64) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut lib = LibraryWriter::create(path)?;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let t = line.trim();
+        if t.is_empty() || t.starts_with('#') { continue; }
+        let start = crate::numexpr::parse_biguint(t).map_err(|e| format!("line {}: {e}", i + 1))?;
+        if start.is_zero() { return Err(format!("line {}: 0 has no Collatz orbit", i + 1).into()); }
+        lib.push(&EncodedOrbit::encode(&start, max_steps))?;
+    }
+    let n = lib.offsets.len() as u64;
+    lib.finish()?;
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lib_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("collatz-codec-{name}-{}.clz", std::process::id()))
+    }
+
+    #[test]
+    fn library_round_trips_every_orbit() {
+        let path = lib_path("round-trip");
+        let input = "27\n# comment\n\n2^10+1\n1\n97\n";
+        assert_eq!(build_library(input.as_bytes(), &path, 50).unwrap(), 4);
+        let mut lib = LibraryReader::open(&path).unwrap();
+        assert_eq!(lib.len(), 4);
+        for (i, (start, steps, reached)) in [(27u32, 50, false), (1025, 36, true), (1, 0, true), (97, 50, false)].into_iter().enumerate() {
+            let orbit = lib.get(i as u64).unwrap();
+            assert_eq!(orbit, EncodedOrbit::encode(&BigUint::from(start), 50));
+            assert_eq!((orbit.steps, orbit.reached_one), (steps, reached), "orbit {i}");
+            // Replay walks the same values as the map itself
+            let mut x = BigUint::from(start);
+            for v in orbit.replay() {
+                assert_eq!(v.unwrap(), x);
+                x = collatz_next(&x);
+            }
+        }
+        assert!(lib.get(4).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_rejects_a_flipped_parity_bit() {
+        let mut orbit = EncodedOrbit::encode(&BigUint::from(27u32), u64::MAX);
+        orbit.parity[1] ^= 0x04;
+        let err = orbit.replay().find_map(Result::err).unwrap();
+        assert_eq!(err, "parity bit 10 does not match the replayed value");
+    }
+
+    #[test]
+    fn unfinished_and_foreign_files_do_not_open() {
+        let path = lib_path("bad");
+        std::fs::write(&path, "not a library").unwrap();
+        assert_eq!(LibraryReader::open(&path).err().unwrap().to_string(), "not an orbit library");
+        let mut lib = LibraryWriter::create(&path).unwrap();
+        lib.push(&EncodedOrbit::encode(&BigUint::from(27u32), u64::MAX)).unwrap();
+        drop(lib); // never finished: no index
+        assert_eq!(LibraryReader::open(&path).err().unwrap().to_string(), "orbit library has no index (unfinished write?)");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_and_oversized_records_are_rejected() {
+        let mut buf = Vec::new();
+        EncodedOrbit::encode(&BigUint::from(27u32), u64::MAX).write_to(&mut buf).unwrap();
+        for cut in 0..buf.len() {
+            assert!(EncodedOrbit::read_from(&mut &buf[..cut], cut as u64).is_err(), "cut at {cut}");
+        }
+        assert_eq!(EncodedOrbit::read_from(&mut buf.as_slice(), buf.len() as u64).unwrap().steps, 111);
+
+        // Lengths far past the data: an error, not an attempt to allocate them
+        let mut huge_start = Vec::new();
+        write_varint(&mut huge_start, u64::MAX).unwrap();
+        let mut huge_steps = vec![1, 5];
+        write_varint(&mut huge_steps, u64::MAX).unwrap();
+        huge_steps.push(0);
+        for bad in [huge_start, huge_steps] {
+            let err = EncodedOrbit::read_from(&mut bad.as_slice(), bad.len() as u64).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn corrupt_library_files_are_rejected() {
+        let path = std::env::temp_dir().join(format!("collatz-codec-{}.clz", std::process::id()));
+        let mut lib = LibraryWriter::create(&path).unwrap();
+        for n in [27u32, 97] { lib.push(&EncodedOrbit::encode(&BigUint::from(n), u64::MAX)).unwrap(); }
+        lib.finish().unwrap();
+        let good = std::fs::read(&path).unwrap();
+        let footer = good.len() - FOOTER_LEN as usize;
+        let index_at = u64::from_le_bytes(good[footer + 8..footer + 16].try_into().unwrap()) as usize;
+
+        let open = |bytes: &[u8]| {
+            std::fs::write(&path, bytes).unwrap();
+            LibraryReader::open(&path)
+        };
+        assert_eq!(open(&good).unwrap().get(1).unwrap().start, BigUint::from(97u32));
+        // Truncated anywhere: the footer is gone
+        for cut in [8, index_at, good.len() - 1] {
+            assert!(open(&good[..cut]).is_err(), "cut at {cut}");
+        }
+        // A record count that would overflow the index size
+        let mut bad = good.clone();
+        bad[footer..footer + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(open(&bad).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        // An offset into the index, and a record whose step count runs into it
+        let mut bad = good.clone();
+        bad[index_at + 8..index_at + 16].copy_from_slice(&(index_at as u64).to_le_bytes());
+        assert_eq!(open(&bad).unwrap().get(1).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut bad = good.clone();
+        let second = u64::from_le_bytes(good[index_at + 8..index_at + 16].try_into().unwrap()) as usize;
+        bad[second + 2] = 0xff; // steps varint of 97's record, now far larger than the file
+        bad[second + 3] = 0x7f;
+        assert_eq!(open(&bad).unwrap().get(1).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
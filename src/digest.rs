@@ -0,0 +1,59 @@
+// ---------- Per-unit result digests for redundant verification ----------
+//
+// Two honest runs over the same unit must produce the same digest, so the
+// coordinator (or `verify-range`) can hand a unit out twice and compare.
+
+use crate::Classified;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a over every start's total stopping time and peak, in scan order
+#[derive(Debug, Clone, Copy)]
+pub struct RangeDigest(u64);
+
+impl RangeDigest {
+    pub fn new() -> Self { RangeDigest(FNV_OFFSET) }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn update(&mut self, c: &Classified) {
+        // Orbits that never reach 1 have no stopping time; u64::MAX marks them
+        self.feed(&c.stopping_time.unwrap_or(u64::MAX).to_le_bytes());
+        let peak = c.peak.to_bytes_le();
+        // Length prefix keeps consecutive peaks from running into each other
+        self.feed(&(peak.len() as u64).to_le_bytes());
+        self.feed(&peak);
+    }
+
+    pub fn hex(&self) -> String { format!("{:016x}", self.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect_outcome;
+    use num_bigint::BigUint;
+
+    fn digest(starts: impl Iterator<Item = u32>) -> String {
+        let mut d = RangeDigest::new();
+        for n in starts { d.update(&detect_outcome(&BigUint::from(n))); }
+        d.hex()
+    }
+
+    #[test]
+    fn same_range_same_digest_and_any_difference_shows() {
+        let base = digest(1..100);
+        assert_eq!(base.len(), 16);
+        assert_eq!(digest(1..100), base);
+        assert_ne!(digest(1..101), base, "one more start");
+        assert_ne!(digest(2..100), base, "one start fewer");
+        assert_ne!(digest((1..100).rev()), base, "scan order is part of the digest");
+        assert_eq!(RangeDigest::new().hex(), format!("{FNV_OFFSET:016x}"));
+    }
+}
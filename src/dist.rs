@@ -0,0 +1,1010 @@
+// ---------- Distributed scanning: coordinator and workers ----------
+//
+// Protocol: one JSON object per line over TCP, one request/response per
+// connection (see json.rs). Workers pull work:
+//
+//   worker -> {"type":"request","worker":W}
+//   coord  -> {"type":"assign","id":N,"kind":"range","start":"S","count":C,"lease_s":L}
+//           | {"type":"assign","id":N,"kind":"random","seed":"X","rng":G,"count":C,"lease_s":L}
+//           | {"type":"wait","retry_s":R} | {"type":"done"}
+//   worker -> {"type":"renew","worker":W,"id":N}            (every lease/3 while working)
+//   worker -> {"type":"result","worker":W,"id":N,"processed":..,"reaches_one":..,...,"digest":D}
+//   coord  -> {"type":"ack","accepted":true|false}
+//
+// A unit whose lease runs out without a renewal or result goes back to the
+// queue and is handed to the next worker that asks. With --verify-every N,
+// every Nth unit is handed out a second time, only ever to a different worker,
+// and the two result digests are compared. A mismatch is recorded as a finding
+// and the unit goes out once more, to a third worker, to settle which run was wrong.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+use crate::digest::RangeDigest;
+use crate::json::{self, Obj, quote};
+use crate::lanes::LaneEngine;
+use crate::metrics::{self, Metrics};
+use crate::numexpr::{parse_biguint, parse_u64};
+use crate::rng::{RandomSource, Rng, RngKind};
+use crate::store::Store;
+use crate::{Classified, DEFAULT_START, LANE_MEMO_MB, Outcome, SHUTDOWN, detect_outcome, install_signal_handler, random_range, write_solution};
+
+type BoxError = Box<dyn std::error::Error>;
+
+/// --start for ranges; 0 never reaches the trivial cycle, so it is not a start
+fn parse_start(s: &str) -> Result<BigUint, String> {
+    let n = parse_biguint(s).map_err(|e| format!("--start: {e}"))?;
+    if n == BigUint::ZERO { return Err("--start: 0 has no Collatz orbit".into()); }
+    Ok(n)
+}
+
+// A piece of work handed out by the coordinator
+#[derive(Debug, Clone)]
+pub enum UnitKind {
+    Range { start: BigUint, count: u64 },
+    Random { seed: u64, rng: RngKind, count: u64 },
+}
+
+// What a worker reports back for one unit
+#[derive(Debug, Clone, Default)]
+pub struct UnitResult {
+    pub processed: u64,
+    pub reaches_one: u64,
+    pub nontrivial_cycle: u64,
+    pub steps_overflow: u64,
+    pub max_steps: u64,
+    pub max_steps_start: Option<BigUint>,
+    pub peak_bits: u64,
+    pub peak_start: Option<BigUint>,
+    pub finding: Option<(String, BigUint)>,
+    pub digest: String,
+}
+
+impl UnitResult {
+    /// Fold one classified start into the counts and records (the digest is kept by the caller)
+    pub fn record(&mut self, start: &BigUint, c: &Classified) {
+        self.processed += 1;
+        match c.outcome {
+            Outcome::ReachesOne => self.reaches_one += 1,
+            Outcome::NontrivialCycle => self.nontrivial_cycle += 1,
+            Outcome::StepsOverflow => self.steps_overflow += 1,
+        }
+        if let Some(t) = c.stopping_time && (self.max_steps_start.is_none() || t > self.max_steps) {
+            self.max_steps = t;
+            self.max_steps_start = Some(start.clone());
+        }
+        let bits = c.peak.bits();
+        if self.peak_start.is_none() || bits > self.peak_bits {
+            self.peak_bits = bits;
+            self.peak_start = Some(start.clone());
+        }
+    }
+
+    pub fn add(&mut self, other: &UnitResult) {
+        self.processed += other.processed;
+        self.reaches_one += other.reaches_one;
+        self.nontrivial_cycle += other.nontrivial_cycle;
+        self.steps_overflow += other.steps_overflow;
+        if other.max_steps_start.is_some() && (self.max_steps_start.is_none() || other.max_steps > self.max_steps) {
+            self.max_steps = other.max_steps;
+            self.max_steps_start = other.max_steps_start.clone();
+        }
+        if other.peak_start.is_some() && (self.peak_start.is_none() || other.peak_bits > self.peak_bits) {
+            self.peak_bits = other.peak_bits;
+            self.peak_start = other.peak_start.clone();
+        }
+    }
+}
+
+/// Classify every start in a unit, stopping early at the first finding like the
+/// main search loop does. Returns None if interrupted by a signal.
+pub fn run_unit(kind: &UnitKind) -> Option<UnitResult> {
+    let mut res = UnitResult::default();
+    let mut digest = RangeDigest::new();
+    let (count, mut next): (u64, Box<dyn FnMut() -> (BigUint, Classified)>) = match kind {
+        // Ranges below 2^64 run on the lane engine; results are the same, just sooner
+        UnitKind::Range { start, count } if let Some(first) = start.to_u64() && first > 0 && first.checked_add(*count).is_some() => {
+            let mut engine = LaneEngine::new(first, first + count, LANE_MEMO_MB << 20);
+            (*count, Box::new(move || {
+                let (n, c) = engine.next_result().expect("the engine covers the whole unit");
+                (BigUint::from(n), c)
+            }))
+        }
+        UnitKind::Range { start, count } => {
+            let mut n = start.clone();
+            (*count, Box::new(move || { let v = n.clone(); n += 1u32; let c = detect_outcome(&v); (v, c) }))
+        }
+        UnitKind::Random { seed, rng, count } => {
+            let mut rng = Rng::new(*rng, *seed);
+            let (low, high) = random_range();
+            (*count, Box::new(move || { let v = rng.gen_range_biguint(&low, &high); let c = detect_outcome(&v); (v, c) }))
+        }
+    };
+    for _ in 0..count {
+        if SHUTDOWN.load(Ordering::Relaxed) { return None; }
+        let (current, c) = next();
+        digest.update(&c);
+        res.record(&current, &c);
+        match c.outcome {
+            Outcome::ReachesOne => {}
+            Outcome::NontrivialCycle => {
+                res.finding = Some(("NONTRIVIAL_CYCLE_START".into(), current));
+                break;
+            }
+            Outcome::StepsOverflow => {
+                res.finding = Some(("RUNAWAY_STEPS_OVERFLOW_START".into(), current));
+                break;
+            }
+        }
+    }
+    res.digest = digest.hex();
+    Some(res)
+}
+
+// ---------- Coordinator ----------
+
+struct CoordConfig {
+    listen: String,
+    start: BigUint,
+    count: Option<u64>,
+    unit_size: u64,
+    random: bool,
+    lease: Duration,
+    solution: String,
+    verify_every: u64, // 0 = no redundant units
+    db: Option<String>,
+    rng: RngKind, // generator random units are sampled with
+    metrics_addr: Option<String>,
+}
+
+fn parse_coordinator_args(mut args: impl Iterator<Item = String>) -> Result<CoordConfig, BoxError> {
+    let mut cfg = CoordConfig {
+        listen: "127.0.0.1:7070".into(),
+        start: parse_biguint(DEFAULT_START)?,
+        count: None,
+        unit_size: 10_000,
+        random: false,
+        lease: Duration::from_secs(60),
+        solution: "solution.txt".into(),
+        verify_every: 0,
+        db: None,
+        rng: RngKind::default(),
+        metrics_addr: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--listen" => cfg.listen = value()?,
+            "--start" | "-s" => cfg.start = parse_start(&value()?)?,
+            "--count" | "-n" => cfg.count = Some(parse_u64(&value()?).map_err(|e| format!("--count: {e}"))?),
+            "--unit-size" => cfg.unit_size = parse_u64(&value()?).map_err(|e| format!("--unit-size: {e}"))?.max(1),
+            "--lease-secs" => cfg.lease = Duration::from_secs(parse_u64(&value()?).map_err(|e| format!("--lease-secs: {e}"))?.max(1)),
+            "--solution" => cfg.solution = value()?,
+            "--verify-every" => cfg.verify_every = parse_u64(&value()?).map_err(|e| format!("--verify-every: {e}"))?,
+            "--db" => cfg.db = Some(value()?),
+            "--metrics-addr" => cfg.metrics_addr = Some(value()?),
+            "--rng" => { let v = value()?; cfg.rng = RngKind::parse(&v).ok_or_else(|| format!("unknown --rng {v}"))? }
+            "--random" => cfg.random = true,
+            "--no-random" => cfg.random = false,
+            other => return Err(format!("unknown coordinator option: {other}").into()),
+        }
+    }
+    Ok(cfg)
+}
+
+#[derive(Clone)]
+struct Unit {
+    id: u64,
+    kind: UnitKind,
+    verify_of: Option<u64>, // Some(original id) for a redundant copy
+}
+
+struct Lease { unit: Unit, worker: String, until: Instant }
+
+// Copies waiting for a worker that has not run the unit yet; once this many
+// pile up, no new units are issued until other workers take some
+const MAX_VERIFY_BACKLOG: usize = 16;
+
+/// An original's digest, until a copy settles it
+struct Check {
+    digest: String,
+    workers: Vec<String>, // everyone who has run the unit, none of whom may verify it
+}
+
+struct CoordState {
+    next_id: u64,
+    issued: u64, // original (non-copy) units created so far
+    next_start: BigUint,
+    remaining: Option<u64>, // starts not yet handed out; None = unbounded
+    pending: VecDeque<Unit>, // expired leases waiting for another worker
+    leased: HashMap<u64, Lease>,
+    // Redundant copies: held back until the original's result is in, then
+    // offered to anyone who has not run the unit yet
+    verify_wait: HashMap<u64, Unit>,
+    verify_ready: VecDeque<Unit>,
+    checks: HashMap<u64, Check>, // keyed by original id
+    verified: u64,
+    mismatches: u64,
+    rng: Rng,
+    totals: UnitResult,
+    units_done: u64,
+    finished: bool,
+    db: Option<Store>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl CoordState {
+    fn expire_leases(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<u64> = self.leased.iter().filter(|(_, l)| l.until <= now).map(|(id, _)| *id).collect();
+        for id in expired {
+            if let Some(l) = self.leased.remove(&id) {
+                eprintln!("lease expired: unit {id} (worker {}), requeueing", l.worker);
+                // Copies keep their list of workers to stay away from
+                if l.unit.verify_of.is_some() {
+                    self.verify_ready.push_back(l.unit);
+                } else {
+                    self.pending.push_back(l.unit);
+                }
+            }
+        }
+    }
+
+    fn next_unit(&mut self, cfg: &CoordConfig, worker: &str) -> Option<Unit> {
+        if let Some(u) = self.pending.pop_front() { return Some(u); }
+        let eligible = |u: &Unit| {
+            u.verify_of.and_then(|o| self.checks.get(&o)).is_none_or(|c| !c.workers.iter().any(|w| w == worker))
+        };
+        if let Some(pos) = self.verify_ready.iter().position(eligible) {
+            return self.verify_ready.remove(pos);
+        }
+        // A worker never verifies its own results; let the copies drain to others first
+        if self.verify_ready.len() >= MAX_VERIFY_BACKLOG { return None; }
+        let size = match self.remaining {
+            Some(0) => return None,
+            Some(r) => r.min(cfg.unit_size),
+            None => cfg.unit_size,
+        };
+        if let Some(ref mut r) = self.remaining { *r -= size; }
+        let kind = if cfg.random {
+            UnitKind::Random { seed: self.rng.next_u64(), rng: cfg.rng, count: size }
+        } else {
+            let start = self.next_start.clone();
+            self.next_start += size;
+            UnitKind::Range { start, count: size }
+        };
+        self.next_id += 1;
+        self.issued += 1;
+        let unit = Unit { id: self.next_id, kind, verify_of: None };
+        if cfg.verify_every > 0 && self.issued.is_multiple_of(cfg.verify_every) {
+            self.next_id += 1;
+            let copy = Unit { id: self.next_id, kind: unit.kind.clone(), verify_of: Some(unit.id) };
+            self.verify_wait.insert(unit.id, copy);
+        }
+        Some(unit)
+    }
+
+    fn all_done(&self) -> bool {
+        self.remaining == Some(0)
+            && self.pending.is_empty()
+            && self.leased.is_empty()
+            && self.verify_wait.is_empty()
+            && self.verify_ready.is_empty()
+    }
+}
+
+pub fn coordinator_main(args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let cfg = Arc::new(parse_coordinator_args(args)?);
+    install_signal_handler();
+    let listener = TcpListener::bind(&cfg.listen)?;
+    eprintln!("Coordinator listening on {}", listener.local_addr()?);
+    if cfg.random {
+        eprintln!("Handing out random units of {} samples ({} rng)", cfg.unit_size, cfg.rng.name());
+    } else {
+        eprintln!("Handing out ranges of {} starting at {}", cfg.unit_size, cfg.start);
+    }
+
+    let state = Arc::new(Mutex::new(CoordState {
+        next_id: 0,
+        issued: 0,
+        next_start: cfg.start.clone(),
+        remaining: cfg.count,
+        pending: VecDeque::new(),
+        leased: HashMap::new(),
+        verify_wait: HashMap::new(),
+        verify_ready: VecDeque::new(),
+        checks: HashMap::new(),
+        verified: 0,
+        mismatches: 0,
+        rng: Rng::seeded(),
+        totals: UnitResult::default(),
+        units_done: 0,
+        finished: false,
+        db: match cfg.db {
+            Some(ref p) => Some(Store::open(Path::new(p))?),
+            None => None,
+        },
+        metrics: match cfg.metrics_addr {
+            Some(ref addr) => {
+                let m = Metrics::new();
+                metrics::serve(addr, m.clone())?;
+                Some(m)
+            }
+            None => None,
+        },
+    }));
+
+    {
+        let (cfg, state) = (cfg.clone(), state.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let (cfg, state) = (cfg.clone(), state.clone());
+                thread::spawn(move || {
+                    if let Err(e) = serve_one(stream, &cfg, &state) {
+                        eprintln!("coordinator: {e}");
+                    }
+                });
+            }
+        });
+    }
+
+    let started = Instant::now();
+    loop {
+        thread::sleep(Duration::from_millis(200));
+        if state.lock().unwrap().finished || SHUTDOWN.load(Ordering::Relaxed) { break; }
+    }
+    // Give polling workers a moment to hear "done" before the listener goes away
+    if !SHUTDOWN.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_secs(2));
+    }
+
+    let st = state.lock().unwrap();
+    let t = &st.totals;
+    let elapsed = started.elapsed().as_secs_f64();
+    eprintln!("---- summary ----");
+    eprintln!("stopped: {}", if SHUTDOWN.load(Ordering::Relaxed) { "interrupted" } else if t.finding.is_some() { "finding reported" } else { "count reached" });
+    eprintln!("units completed: {} ({} still leased, {} queued)", st.units_done, st.leased.len(), st.pending.len());
+    eprintln!("processed: {} starts in {elapsed:.1}s ({:.1} starts/s)", t.processed, t.processed as f64 / elapsed.max(1e-9));
+    eprintln!("outcomes: reaches_one={} nontrivial_cycle={} steps_overflow={}", t.reaches_one, t.nontrivial_cycle, t.steps_overflow);
+    eprintln!("max stopping time: {}", t.max_steps);
+    if cfg.verify_every > 0 {
+        eprintln!("verified units: {} ({} digest mismatches)", st.verified, st.mismatches);
+    }
+    if !cfg.random {
+        eprintln!("next unassigned start: {}", st.next_start);
+    }
+    Ok(())
+}
+
+fn serve_one(stream: TcpStream, cfg: &CoordConfig, state: &Mutex<CoordState>) -> Result<(), BoxError> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let reply = match json::parse_object(&line) {
+        Ok(msg) => handle_message(&msg, cfg, state).unwrap_or_else(|e| format!("{{\"type\":\"error\",\"message\":{}}}", quote(&e))),
+        Err(e) => format!("{{\"type\":\"error\",\"message\":{}}}", quote(&format!("bad request: {e}"))),
+    };
+    let mut stream = stream;
+    writeln!(stream, "{reply}")?;
+    Ok(())
+}
+
+fn handle_message(msg: &Obj, cfg: &CoordConfig, state: &Mutex<CoordState>) -> Result<String, String> {
+    let worker = msg.str("worker")?.to_string();
+    let mut st = state.lock().unwrap();
+    st.expire_leases();
+    match msg.str("type")? {
+        "request" => {
+            if st.finished { return Ok("{\"type\":\"done\"}".into()); }
+            let Some(unit) = st.next_unit(cfg, &worker) else {
+                // Everything is handed out (or the copies are waiting for someone
+                // else); idle workers wait in case a lease expires
+                return Ok(if st.all_done() {
+                    "{\"type\":\"done\"}".into()
+                } else {
+                    "{\"type\":\"wait\",\"retry_s\":1}".into()
+                });
+            };
+            let lease_s = cfg.lease.as_secs();
+            let reply = match &unit.kind {
+                UnitKind::Range { start, count } => format!(
+                    "{{\"type\":\"assign\",\"id\":{},\"kind\":\"range\",\"start\":\"{start}\",\"count\":{count},\"lease_s\":{lease_s}}}",
+                    unit.id
+                ),
+                UnitKind::Random { seed, rng, count } => format!(
+                    "{{\"type\":\"assign\",\"id\":{},\"kind\":\"random\",\"seed\":\"{seed}\",\"rng\":{},\"count\":{count},\"lease_s\":{lease_s}}}",
+                    unit.id, quote(rng.name())
+                ),
+            };
+            match unit.verify_of {
+                Some(orig) => eprintln!("assigned unit {} (verifying unit {orig}) to {worker}", unit.id),
+                None => eprintln!("assigned unit {} to {worker}", unit.id),
+            }
+            st.leased.insert(unit.id, Lease { unit, worker, until: Instant::now() + cfg.lease });
+            Ok(reply)
+        }
+        "renew" => {
+            let id = msg.u64("id")?;
+            let accepted = match st.leased.get_mut(&id) {
+                Some(l) if l.worker == worker => { l.until = Instant::now() + cfg.lease; true }
+                _ => false,
+            };
+            Ok(format!("{{\"type\":\"ack\",\"accepted\":{accepted}}}"))
+        }
+        "result" => {
+            let id = msg.u64("id")?;
+            // A late result for an expired lease still counts if the unit is back in
+            // the queue; once another worker holds it, only that worker's result does
+            let unit = match st.leased.get(&id) {
+                Some(l) if l.worker == worker => st.leased.remove(&id).map(|l| l.unit),
+                Some(_) => None,
+                None => match st.pending.iter().position(|u| u.id == id) {
+                    Some(pos) => st.pending.remove(pos),
+                    None => st.verify_ready.iter().position(|u| u.id == id).and_then(|pos| st.verify_ready.remove(pos)),
+                },
+            };
+            let Some(unit) = unit else {
+                return Ok("{\"type\":\"ack\",\"accepted\":false}".into());
+            };
+            let res = parse_result(msg)?;
+
+            if let Some(orig) = unit.verify_of {
+                // Redundant copy: compare, but never count the starts twice
+                check_copy(&mut st, orig, unit, &worker, &res.digest)?;
+                if st.all_done() { st.finished = true; }
+                return Ok("{\"type\":\"ack\",\"accepted\":true}".into());
+            }
+
+            if let Some(copy) = st.verify_wait.remove(&id) {
+                st.checks.insert(id, Check { digest: res.digest.clone(), workers: vec![worker.clone()] });
+                st.verify_ready.push_back(copy);
+            }
+            st.totals.add(&res);
+            st.units_done += 1;
+            if let Some(ref m) = st.metrics { m.record_unit(&res); }
+            if let Some(ref mut db) = st.db {
+                record_unit(db, &unit.kind, &res).map_err(|e| format!("results database: {e}"))?;
+            }
+            eprintln!(
+                "unit {id} done by {worker}: {} starts, digest {} (total {} in {} units)",
+                res.processed, res.digest, st.totals.processed, st.units_done
+            );
+            if let Some((kind, start)) = res.finding {
+                eprintln!("Worker {worker} reported {kind} {start}");
+                write_solution(Path::new(&cfg.solution), &format!("{kind} {start}")).map_err(|e| e.to_string())?;
+                st.totals.finding = Some((kind, start));
+                st.finished = true;
+            }
+            if st.all_done() { st.finished = true; }
+            Ok("{\"type\":\"ack\",\"accepted\":true}".into())
+        }
+        other => Err(format!("unknown message type: {other}")),
+    }
+}
+
+/// Compare a copy's digest with the original's. A mismatch is journaled as a
+/// finding and, the first time, the unit goes out again to a worker that has not
+/// run it; that third result shows which of the first two was wrong.
+fn check_copy(st: &mut CoordState, orig: u64, unit: Unit, worker: &str, digest: &str) -> Result<(), String> {
+    let Some(mut check) = st.checks.remove(&orig) else { return Ok(()) };
+    if check.digest == digest {
+        st.verified += 1;
+        if let Some(ref m) = st.metrics { m.record_check(true); }
+        eprintln!("unit {orig} verified by {worker} (digest {digest})");
+        return Ok(());
+    }
+    st.mismatches += 1;
+    if let Some(ref m) = st.metrics { m.record_check(false); }
+    eprintln!("DIGEST MISMATCH for unit {orig}: original {}, {worker} got {digest} ({:?})", check.digest, unit.kind);
+    if let Some(ref mut db) = st.db {
+        let (kind, at) = match &unit.kind {
+            UnitKind::Range { start, .. } => ("DIGEST_MISMATCH", start.clone()),
+            UnitKind::Random { seed, .. } => ("DIGEST_MISMATCH_SEED", BigUint::from(*seed)),
+        };
+        db.add_finding(kind, &at).map_err(|e| format!("results database: {e}"))?;
+    }
+    check.workers.push(worker.to_string());
+    if check.workers.len() < 3 {
+        st.next_id += 1;
+        let again = Unit { id: st.next_id, kind: unit.kind, verify_of: Some(orig) };
+        eprintln!("re-issuing unit {orig} as unit {} to settle the mismatch", again.id);
+        st.verify_ready.push_back(again);
+        st.checks.insert(orig, check);
+    }
+    Ok(())
+}
+
+/// Journal an accepted original unit: ranges add coverage, random units only records
+fn record_unit(db: &mut Store, kind: &UnitKind, res: &UnitResult) -> std::io::Result<()> {
+    match kind {
+        UnitKind::Range { start, .. } => {
+            // The start that produced a finding is not verified, only the ones before it
+            let mut covered = res.clone();
+            if res.finding.is_some() { covered.processed -= 1; }
+            db.add_range(start, &covered)?;
+        }
+        UnitKind::Random { .. } => db.add_samples(res)?,
+    }
+    if let Some((ref kind, ref start)) = res.finding { db.add_finding(kind, start)?; }
+    Ok(())
+}
+
+fn parse_result(msg: &Obj) -> Result<UnitResult, String> {
+    let finding = match (msg.opt("finding_kind"), msg.opt("finding_start")) {
+        (Some(k), Some(s)) => Some((k.to_string(), s.parse::<BigUint>().map_err(|_| "invalid finding_start")?)),
+        _ => None,
+    };
+    let big = |key: &str| -> Result<Option<BigUint>, String> {
+        msg.opt(key).map(|v| v.parse::<BigUint>().map_err(|_| format!("invalid {key}"))).transpose()
+    };
+    Ok(UnitResult {
+        processed: msg.u64("processed")?,
+        reaches_one: msg.u64("reaches_one")?,
+        nontrivial_cycle: msg.u64("nontrivial_cycle")?,
+        steps_overflow: msg.u64("steps_overflow")?,
+        max_steps: msg.u64("max_steps")?,
+        max_steps_start: big("max_steps_start")?,
+        peak_bits: msg.u64("peak_bits")?,
+        peak_start: big("peak_start")?,
+        finding,
+        digest: msg.str("digest")?.to_string(),
+    })
+}
+
+// ---------- Worker ----------
+
+/// Send one request line and read one reply line
+fn call(addr: &str, line: &str) -> Result<Obj, BoxError> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    writeln!(stream, "{line}")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    let obj = json::parse_object(&reply)?;
+    if obj.opt("type") == Some("error") {
+        return Err(format!("coordinator error: {}", obj.opt("message").unwrap_or("?")).into());
+    }
+    Ok(obj)
+}
+
+/// `call` with retries so a coordinator restart or hiccup does not kill the worker
+fn call_retry(addr: &str, line: &str) -> Result<Obj, BoxError> {
+    let mut delay = Duration::from_millis(250);
+    for attempt in 1.. {
+        match call(addr, line) {
+            Ok(obj) => return Ok(obj),
+            Err(e) if attempt < 8 && !SHUTDOWN.load(Ordering::Relaxed) => {
+                eprintln!("coordinator unreachable ({e}), retrying in {:.1}s", delay.as_secs_f64());
+                thread::sleep(delay);
+                delay = (delay * 2).min(Duration::from_secs(10));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+pub fn worker_main(mut args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let mut addr = String::from("127.0.0.1:7070");
+    let mut name = format!("worker-{}", std::process::id());
+    let mut metrics_addr = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--coordinator" => addr = args.next().ok_or("--coordinator needs a value")?,
+            "--name" => name = args.next().ok_or("--name needs a value")?,
+            "--metrics-addr" => metrics_addr = Some(args.next().ok_or("--metrics-addr needs a value")?),
+            other => return Err(format!("unknown worker option: {other}").into()),
+        }
+    }
+    install_signal_handler();
+    eprintln!("Worker {name} pulling work from {addr}");
+    let metrics = match metrics_addr {
+        Some(ref a) => {
+            let m = Metrics::new();
+            metrics::serve(a, m.clone())?;
+            Some(m)
+        }
+        None => None,
+    };
+
+    let request = format!("{{\"type\":\"request\",\"worker\":{}}}", quote(&name));
+    let mut units = 0u64;
+    while !SHUTDOWN.load(Ordering::Relaxed) {
+        let msg = call_retry(&addr, &request)?;
+        match msg.str("type")? {
+            "assign" => {}
+            "wait" => {
+                thread::sleep(Duration::from_secs(msg.u64("retry_s").unwrap_or(1)));
+                continue;
+            }
+            "done" => break,
+            other => return Err(format!("unexpected reply: {other}").into()),
+        }
+        let id = msg.u64("id")?;
+        let count = msg.u64("count")?;
+        let kind = match msg.str("kind")? {
+            "range" => UnitKind::Range { start: msg.str("start")?.parse().map_err(|_| "invalid start")?, count },
+            "random" => {
+                // Coordinators that predate --rng leave it out
+                let rng = match msg.opt("rng") {
+                    Some(g) => RngKind::parse(g).ok_or_else(|| format!("unknown rng: {g}"))?,
+                    None => RngKind::default(),
+                };
+                UnitKind::Random { seed: msg.str("seed")?.parse().map_err(|_| "invalid seed")?, rng, count }
+            }
+            other => return Err(format!("unknown unit kind: {other}").into()),
+        };
+        let lease = Duration::from_secs(msg.u64("lease_s").unwrap_or(60).max(1));
+
+        // Keep the lease alive from a side thread while this one computes
+        let working = Arc::new(AtomicBool::new(true));
+        let renewer = {
+            let (working, addr) = (working.clone(), addr.clone());
+            let renew = format!("{{\"type\":\"renew\",\"worker\":{},\"id\":{id}}}", quote(&name));
+            thread::spawn(move || {
+                let tick = Duration::from_millis(100);
+                let mut waited = Duration::ZERO;
+                while working.load(Ordering::Relaxed) {
+                    thread::sleep(tick);
+                    waited += tick;
+                    if waited >= lease / 3 {
+                        waited = Duration::ZERO;
+                        if let Err(e) = call(&addr, &renew) { eprintln!("lease renewal failed: {e}"); }
+                    }
+                }
+            })
+        };
+        let unit_started = Instant::now();
+        let result = run_unit(&kind);
+        working.store(false, Ordering::Relaxed);
+        let _ = renewer.join();
+
+        // Interrupted: drop the unit, its lease will expire and it gets reassigned
+        let Some(res) = result else { break };
+        if let Some(ref m) = metrics {
+            m.record_unit(&res);
+            m.set_sps(res.processed as f64 / unit_started.elapsed().as_secs_f64().max(1e-9));
+        }
+        let (fk, fs) = match res.finding {
+            Some((ref k, ref s)) => (quote(k), format!("\"{s}\"")),
+            None => ("null".into(), "null".into()),
+        };
+        let big = |n: &Option<BigUint>| n.as_ref().map(|n| format!("\"{n}\"")).unwrap_or_else(|| "null".into());
+        let line = format!(
+            "{{\"type\":\"result\",\"worker\":{},\"id\":{id},\"processed\":{},\"reaches_one\":{},\"nontrivial_cycle\":{},\"steps_overflow\":{},\"max_steps\":{},\"max_steps_start\":{},\"peak_bits\":{},\"peak_start\":{},\"finding_kind\":{fk},\"finding_start\":{fs},\"digest\":\"{}\"}}",
+            quote(&name), res.processed, res.reaches_one, res.nontrivial_cycle, res.steps_overflow,
+            res.max_steps, big(&res.max_steps_start), res.peak_bits, big(&res.peak_start), res.digest
+        );
+        let ack = call_retry(&addr, &line)?;
+        units += 1;
+        if ack.opt("accepted") != Some("true") {
+            eprintln!("unit {id} result was not accepted (lease lost)");
+        } else {
+            eprintln!("unit {id}: {} starts", res.processed);
+        }
+    }
+    eprintln!("Worker {name} finished after {units} units");
+    Ok(())
+}
+
+// ---------- verify-range ----------
+
+/// Recompute one unit locally and print its digest; with --expect, fail on mismatch.
+/// Ranges take --start/--count, random units --seed/--count and --rng if not the
+/// default (same numbers the coordinator logs).
+pub fn verify_range_main(mut args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let mut start: Option<BigUint> = None;
+    let mut seed: Option<u64> = None;
+    let mut rng = RngKind::default();
+    let mut count: Option<u64> = None;
+    let mut expect: Option<String> = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--start" | "-s" => start = Some(parse_start(&value()?)?),
+            "--seed" => seed = Some(parse_u64(&value()?).map_err(|e| format!("--seed: {e}"))?),
+            "--rng" => { let v = value()?; rng = RngKind::parse(&v).ok_or_else(|| format!("unknown --rng {v}"))? }
+            "--count" | "-n" => count = Some(parse_u64(&value()?).map_err(|e| format!("--count: {e}"))?),
+            "--expect" => expect = Some(value()?.to_ascii_lowercase()),
+            other => return Err(format!("unknown verify-range option: {other}").into()),
+        }
+    }
+    let count = count.ok_or("verify-range needs --count")?;
+    let kind = match (start, seed) {
+        (Some(start), None) => UnitKind::Range { start, count },
+        (None, Some(seed)) => UnitKind::Random { seed, rng, count },
+        _ => return Err("verify-range needs exactly one of --start or --seed".into()),
+    };
+    install_signal_handler();
+    let res = run_unit(&kind).ok_or("interrupted")?;
+    println!(
+        "processed={} reaches_one={} nontrivial_cycle={} steps_overflow={} max_steps={} digest={}",
+        res.processed, res.reaches_one, res.nontrivial_cycle, res.steps_overflow, res.max_steps, res.digest
+    );
+    if let Some((kind, start)) = res.finding {
+        println!("finding: {kind} {start}");
+    }
+    match expect {
+        Some(e) if e != res.digest => Err(format!("digest mismatch: expected {e}, got {}", res.digest).into()),
+        Some(_) => { eprintln!("digest matches"); Ok(()) }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(count: Option<u64>) -> CoordConfig {
+        CoordConfig {
+            listen: String::new(),
+            start: BigUint::from(1u32),
+            count,
+            unit_size: 10,
+            random: false,
+            lease: Duration::from_secs(60),
+            solution: std::env::temp_dir().join(format!("collatz-dist-{}.txt", std::process::id())).display().to_string(),
+            verify_every: 0,
+            db: None,
+            rng: RngKind::default(),
+            metrics_addr: None,
+        }
+    }
+
+    fn state(cfg: &CoordConfig) -> Mutex<CoordState> {
+        Mutex::new(CoordState {
+            next_id: 0,
+            issued: 0,
+            next_start: cfg.start.clone(),
+            remaining: cfg.count,
+            pending: VecDeque::new(),
+            leased: HashMap::new(),
+            verify_wait: HashMap::new(),
+            verify_ready: VecDeque::new(),
+            checks: HashMap::new(),
+            verified: 0,
+            mismatches: 0,
+            rng: Rng::from_seed(32),
+            totals: UnitResult::default(),
+            units_done: 0,
+            finished: false,
+            db: None,
+            metrics: Some(Metrics::new()),
+        })
+    }
+
+    fn send(cfg: &CoordConfig, st: &Mutex<CoordState>, line: &str) -> Obj {
+        let reply = handle_message(&json::parse_object(line).unwrap(), cfg, st).unwrap();
+        json::parse_object(&reply).unwrap()
+    }
+
+    /// Ask for work; the assignment, or None for wait/done
+    fn request(cfg: &CoordConfig, st: &Mutex<CoordState>, worker: &str) -> Option<Obj> {
+        let reply = send(cfg, st, &format!("{{\"type\":\"request\",\"worker\":\"{worker}\"}}"));
+        (reply.opt("type") == Some("assign")).then_some(reply)
+    }
+
+    /// Report a clean unit of `processed` starts; whether the coordinator accepted it
+    fn report(cfg: &CoordConfig, st: &Mutex<CoordState>, worker: &str, id: u64, processed: u64) -> bool {
+        report_digest(cfg, st, worker, id, processed, "00")
+    }
+
+    fn report_digest(cfg: &CoordConfig, st: &Mutex<CoordState>, worker: &str, id: u64, processed: u64, digest: &str) -> bool {
+        let line = format!(
+            "{{\"type\":\"result\",\"worker\":\"{worker}\",\"id\":{id},\"processed\":{processed},\"reaches_one\":{processed},\
+             \"nontrivial_cycle\":0,\"steps_overflow\":0,\"max_steps\":19,\"peak_bits\":5,\"finding_kind\":null,\"finding_start\":null,\"digest\":\"{digest}\"}}"
+        );
+        send(cfg, st, &line).opt("accepted") == Some("true")
+    }
+
+    fn expire(st: &Mutex<CoordState>, id: u64) {
+        st.lock().unwrap().leased.get_mut(&id).unwrap().until = Instant::now();
+    }
+
+    #[test]
+    fn ranges_are_handed_out_in_order_until_the_count_is_covered() {
+        let cfg = config(Some(25));
+        let st = state(&cfg);
+        let units: Vec<(u64, String, u64)> = (0..3)
+            .map(|_| {
+                let a = request(&cfg, &st, "a").unwrap();
+                (a.u64("id").unwrap(), a.str("start").unwrap().to_string(), a.u64("count").unwrap())
+            })
+            .collect();
+        assert_eq!(units, [(1, "1".into(), 10), (2, "11".into(), 10), (3, "21".into(), 5)]);
+        // Everything is leased: wait, in case a lease runs out
+        assert_eq!(send(&cfg, &st, "{\"type\":\"request\",\"worker\":\"b\"}").opt("type"), Some("wait"));
+        for (id, _, count) in units { assert!(report(&cfg, &st, "a", id, count)); }
+        let done = st.lock().unwrap();
+        assert_eq!((done.units_done, done.totals.processed, done.finished), (3, 25, true));
+        drop(done);
+        assert_eq!(send(&cfg, &st, "{\"type\":\"request\",\"worker\":\"b\"}").opt("type"), Some("done"));
+    }
+
+    #[test]
+    fn expired_lease_goes_to_the_next_worker() {
+        let cfg = config(Some(10));
+        let st = state(&cfg);
+        assert_eq!(request(&cfg, &st, "a").unwrap().u64("id"), Ok(1));
+        assert!(request(&cfg, &st, "b").is_none(), "everything is leased");
+        expire(&st, 1);
+        assert_eq!(request(&cfg, &st, "b").unwrap().u64("id"), Ok(1));
+        assert_eq!(st.lock().unwrap().leased[&1].worker, "b");
+    }
+
+    #[test]
+    fn only_the_holder_renews_a_lease() {
+        let cfg = config(Some(10));
+        let st = state(&cfg);
+        request(&cfg, &st, "a");
+        let renew = |worker: &str| send(&cfg, &st, &format!("{{\"type\":\"renew\",\"worker\":\"{worker}\",\"id\":1}}"));
+        assert_eq!(renew("b").opt("accepted"), Some("false"));
+        assert_eq!(renew("a").opt("accepted"), Some("true"));
+        assert!(st.lock().unwrap().leased[&1].until > Instant::now() + Duration::from_secs(59));
+    }
+
+    #[test]
+    fn stale_result_leaves_the_new_lease_alone() {
+        let cfg = config(Some(10));
+        let st = state(&cfg);
+        request(&cfg, &st, "a");
+        expire(&st, 1);
+        request(&cfg, &st, "b");
+        // a's lease ran out and the unit is b's now
+        assert!(!report(&cfg, &st, "a", 1, 10));
+        assert_eq!(st.lock().unwrap().leased[&1].worker, "b");
+        assert!(report(&cfg, &st, "b", 1, 10));
+        let st = st.lock().unwrap();
+        assert_eq!((st.units_done, st.totals.processed, st.finished), (1, 10, true));
+    }
+
+    #[test]
+    fn late_result_counts_while_the_unit_is_queued() {
+        let cfg = config(Some(10));
+        let st = state(&cfg);
+        request(&cfg, &st, "a");
+        expire(&st, 1);
+        assert!(report(&cfg, &st, "a", 1, 10));
+        let st = st.lock().unwrap();
+        assert!(st.pending.is_empty() && st.finished);
+        assert_eq!(st.units_done, 1);
+    }
+
+    #[test]
+    fn a_finding_stops_the_run_and_is_saved() {
+        let cfg = config(None);
+        let st = state(&cfg);
+        request(&cfg, &st, "a");
+        let line = "{\"type\":\"result\",\"worker\":\"a\",\"id\":1,\"processed\":3,\"reaches_one\":2,\"nontrivial_cycle\":1,\
+                    \"steps_overflow\":0,\"max_steps\":19,\"peak_bits\":5,\"finding_kind\":\"NONTRIVIAL_CYCLE_START\",\"finding_start\":\"3\",\"digest\":\"00\"}";
+        assert_eq!(send(&cfg, &st, line).opt("accepted"), Some("true"));
+        assert!(st.lock().unwrap().finished);
+        assert_eq!(send(&cfg, &st, "{\"type\":\"request\",\"worker\":\"b\"}").opt("type"), Some("done"));
+        assert_eq!(std::fs::read_to_string(&cfg.solution).unwrap().trim(), "NONTRIVIAL_CYCLE_START 3");
+        std::fs::remove_file(&cfg.solution).unwrap();
+    }
+
+    #[test]
+    fn copies_go_to_another_worker_and_digests_are_compared() {
+        let mut cfg = config(Some(20));
+        cfg.verify_every = 1;
+        let st = state(&cfg);
+        assert_eq!(request(&cfg, &st, "a").unwrap().u64("id"), Ok(1));
+        assert_eq!(request(&cfg, &st, "a").unwrap().u64("id"), Ok(3));
+        // Copies are held back until the original reports
+        assert!(request(&cfg, &st, "b").is_none());
+        assert!(report_digest(&cfg, &st, "a", 1, 10, "aa"));
+        assert!(report_digest(&cfg, &st, "a", 3, 10, "bb"));
+        let copy = request(&cfg, &st, "b").unwrap();
+        assert_eq!((copy.u64("id"), copy.str("start")), (Ok(2), Ok("1")));
+        assert!(report_digest(&cfg, &st, "b", 2, 10, "aa"));
+        assert_eq!(request(&cfg, &st, "b").unwrap().u64("id"), Ok(4));
+        assert!(report_digest(&cfg, &st, "b", 4, 10, "cc"));
+        // The mismatch sends unit 3 out once more, to neither a nor b
+        assert!(request(&cfg, &st, "a").is_none() && request(&cfg, &st, "b").is_none());
+        let again = request(&cfg, &st, "c").unwrap();
+        assert_eq!((again.u64("id"), again.str("start")), (Ok(5), Ok("11")));
+        assert!(report_digest(&cfg, &st, "c", 5, 10, "bb"));
+        let st = st.lock().unwrap();
+        // The copies' starts are not counted twice
+        assert_eq!((st.verified, st.mismatches, st.totals.processed, st.units_done), (2, 1, 20, 2));
+        assert!(st.finished);
+        let text = st.metrics.as_ref().unwrap().render();
+        for line in ["collatz_starts_processed_total 20", "collatz_units_completed_total 2", "collatz_units_verified_total 2", "collatz_digest_mismatches_total 1"] {
+            assert!(text.lines().any(|l| l == line), "{line} missing from\n{text}");
+        }
+    }
+
+    #[test]
+    fn a_lone_worker_never_gets_its_own_copies_and_stops_at_the_backlog() {
+        let mut cfg = config(None);
+        cfg.verify_every = 1;
+        let st = state(&cfg);
+        let mut originals = 0;
+        while let Some(unit) = request(&cfg, &st, "a") {
+            assert_eq!(unit.u64("id"), Ok(2 * originals + 1), "only originals go to a");
+            assert!(report(&cfg, &st, "a", unit.u64("id").unwrap(), 10));
+            originals += 1;
+        }
+        assert_eq!(originals, MAX_VERIFY_BACKLOG as u64);
+        assert_eq!(send(&cfg, &st, "{\"type\":\"request\",\"worker\":\"a\"}").opt("type"), Some("wait"));
+        // A second worker drains the copies, oldest first, which lets a go on
+        assert_eq!(request(&cfg, &st, "b").unwrap().u64("id"), Ok(2));
+        assert_eq!(request(&cfg, &st, "a").unwrap().u64("id"), Ok(2 * originals + 1));
+    }
+
+    #[test]
+    fn an_expired_copy_still_avoids_the_original_worker() {
+        let mut cfg = config(Some(10));
+        cfg.verify_every = 1;
+        let st = state(&cfg);
+        request(&cfg, &st, "a");
+        assert!(report(&cfg, &st, "a", 1, 10));
+        assert_eq!(request(&cfg, &st, "b").unwrap().u64("id"), Ok(2));
+        expire(&st, 2);
+        assert_eq!(send(&cfg, &st, "{\"type\":\"request\",\"worker\":\"a\"}").opt("type"), Some("wait"));
+        assert_eq!(request(&cfg, &st, "c").unwrap().u64("id"), Ok(2));
+        assert!(report(&cfg, &st, "c", 2, 10));
+        assert!(st.lock().unwrap().finished);
+    }
+
+    #[test]
+    fn a_mismatch_is_journaled_as_a_finding() {
+        let mut cfg = config(Some(10));
+        cfg.verify_every = 1;
+        let st = state(&cfg);
+        let path = std::env::temp_dir().join(format!("collatz-dist-mismatch-{}.db", std::process::id()));
+        st.lock().unwrap().db = Some(Store::open(&path).unwrap());
+        request(&cfg, &st, "a");
+        assert!(report_digest(&cfg, &st, "a", 1, 10, "aa"));
+        request(&cfg, &st, "b");
+        assert!(report_digest(&cfg, &st, "b", 2, 10, "bb"));
+        let journal = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(journal.lines().any(|l| l.starts_with("finding DIGEST_MISMATCH 1 ")), "{journal}");
+        assert_eq!(st.lock().unwrap().verify_ready.front().map(|u| (u.id, u.verify_of)), Some((3, Some(1))));
+    }
+
+    #[test]
+    fn verify_every_n_copies_every_nth_unit() {
+        let mut cfg = config(None);
+        cfg.verify_every = 3;
+        let st = state(&cfg);
+        let ids: Vec<u64> = (0..6).map(|_| request(&cfg, &st, "a").unwrap().u64("id").unwrap()).collect();
+        assert_eq!(ids, [1, 2, 3, 5, 6, 7]);
+        assert_eq!(st.lock().unwrap().verify_wait.keys().copied().collect::<std::collections::BTreeSet<_>>(), [3, 7].into());
+    }
+
+    #[test]
+    fn accepted_ranges_and_findings_are_journaled() {
+        let mut cfg = config(Some(20));
+        // Its own solution file: a_finding_stops_the_run_and_is_saved may run alongside
+        cfg.solution = std::env::temp_dir().join(format!("collatz-dist-db-{}.txt", std::process::id())).display().to_string();
+        let st = state(&cfg);
+        let path = std::env::temp_dir().join(format!("collatz-dist-db-{}.db", std::process::id()));
+        st.lock().unwrap().db = Some(Store::open(&path).unwrap());
+        request(&cfg, &st, "a");
+        request(&cfg, &st, "a");
+        assert!(report(&cfg, &st, "a", 1, 10));
+        let line = "{\"type\":\"result\",\"worker\":\"a\",\"id\":2,\"processed\":3,\"reaches_one\":2,\"nontrivial_cycle\":1,\
+                    \"steps_overflow\":0,\"max_steps\":19,\"peak_bits\":5,\"finding_kind\":\"NONTRIVIAL_CYCLE_START\",\"finding_start\":\"13\",\"digest\":\"00\"}";
+        assert_eq!(send(&cfg, &st, line).opt("accepted"), Some("true"));
+        let journal = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&cfg.solution).unwrap();
+        let entries: Vec<String> = journal.lines().skip(1).map(|l| l.split(' ').take(3).collect::<Vec<_>>().join(" ")).collect();
+        // The start that produced the finding is not counted as covered
+        assert_eq!(entries, ["range 1 10", "range 11 2", "finding NONTRIVIAL_CYCLE_START 13"]);
+    }
+}
@@ -0,0 +1,215 @@
+// ---------- Small bitmap text rendering (5x7, printable ASCII) ----------
+
+use crate::viz::plot;
+
+pub const SMALL_FONT_W: usize = 5;
+pub const SMALL_FONT_H: usize = 7;
+/// Horizontal advance per character (glyph width + spacing), unscaled
+pub const SMALL_ADVANCE: usize = SMALL_FONT_W + 2;
+/// Vertical advance per text line (glyph height + spacing), unscaled
+pub const SMALL_LINE_H: usize = SMALL_FONT_H + 3;
+
+// Glyphs for ' ' (0x20) through '~' (0x7E). Each row uses the lower 5 bits,
+// MSB of the 5 is the leftmost pixel.
+const FONT_5X7: [[u8; SMALL_FONT_H]; 95] = [
+    [0b00000,0b00000,0b00000,0b00000,0b00000,0b00000,0b00000], // ' '
+    [0b00100,0b00100,0b00100,0b00100,0b00100,0b00000,0b00100], // '!'
+    [0b01010,0b01010,0b01010,0b00000,0b00000,0b00000,0b00000], // '"'
+    [0b01010,0b01010,0b11111,0b01010,0b11111,0b01010,0b01010], // '#'
+    [0b00100,0b01111,0b10100,0b01110,0b00101,0b11110,0b00100], // '$'
+    [0b11000,0b11001,0b00010,0b00100,0b01000,0b10011,0b00011], // '%'
+    [0b01100,0b10010,0b10100,0b01000,0b10101,0b10010,0b01101], // '&'
+    [0b01100,0b00100,0b01000,0b00000,0b00000,0b00000,0b00000], // '\''
+    [0b00010,0b00100,0b01000,0b01000,0b01000,0b00100,0b00010], // '('
+    [0b01000,0b00100,0b00010,0b00010,0b00010,0b00100,0b01000], // ')'
+    [0b00000,0b00100,0b10101,0b01110,0b10101,0b00100,0b00000], // '*'
+    [0b00000,0b00100,0b00100,0b11111,0b00100,0b00100,0b00000], // '+'
+    [0b00000,0b00000,0b00000,0b00000,0b01100,0b00100,0b01000], // ','
+    [0b00000,0b00000,0b00000,0b11111,0b00000,0b00000,0b00000], // '-'
+    [0b00000,0b00000,0b00000,0b00000,0b00000,0b00100,0b00100], // '.'
+    [0b00000,0b00001,0b00010,0b00100,0b01000,0b10000,0b00000], // '/'
+    [0b01110,0b10001,0b10011,0b10101,0b11001,0b10001,0b01110], // '0'
+    [0b00100,0b01100,0b00100,0b00100,0b00100,0b00100,0b01110], // '1'
+    [0b01110,0b10001,0b00001,0b00010,0b00100,0b01000,0b11111], // '2'
+    [0b01110,0b10001,0b00001,0b00110,0b00001,0b10001,0b01110], // '3'
+    [0b00010,0b00110,0b01010,0b10010,0b11111,0b00010,0b00010], // '4'
+    [0b11111,0b10000,0b11110,0b00001,0b00001,0b10001,0b01110], // '5'
+    [0b00110,0b01000,0b10000,0b11110,0b10001,0b10001,0b01110], // '6'
+    [0b11111,0b00001,0b00010,0b00100,0b01000,0b01000,0b01000], // '7'
+    [0b01110,0b10001,0b10001,0b01110,0b10001,0b10001,0b01110], // '8'
+    [0b01110,0b10001,0b10001,0b01111,0b00001,0b00010,0b11100], // '9'
+    [0b00000,0b00100,0b00100,0b00000,0b00100,0b00100,0b00000], // ':'
+    [0b00000,0b01100,0b01100,0b00000,0b01100,0b00100,0b01000], // ';'
+    [0b00010,0b00100,0b01000,0b10000,0b01000,0b00100,0b00010], // '<'
+    [0b00000,0b00000,0b11111,0b00000,0b11111,0b00000,0b00000], // '='
+    [0b01000,0b00100,0b00010,0b00001,0b00010,0b00100,0b01000], // '>'
+    [0b01110,0b10001,0b00001,0b00010,0b00100,0b00000,0b00100], // '?'
+    [0b01110,0b10001,0b00001,0b01101,0b10101,0b10101,0b01110], // '@'
+    [0b01110,0b10001,0b10001,0b10001,0b11111,0b10001,0b10001], // 'A'
+    [0b11110,0b10001,0b10001,0b11110,0b10001,0b10001,0b11110], // 'B'
+    [0b01110,0b10001,0b10000,0b10000,0b10000,0b10001,0b01110], // 'C'
+    [0b11110,0b10001,0b10001,0b10001,0b10001,0b10001,0b11110], // 'D'
+    [0b11111,0b10000,0b10000,0b11110,0b10000,0b10000,0b11111], // 'E'
+    [0b11111,0b10000,0b10000,0b11110,0b10000,0b10000,0b10000], // 'F'
+    [0b01110,0b10001,0b10000,0b10111,0b10001,0b10001,0b01110], // 'G'
+    [0b10001,0b10001,0b10001,0b11111,0b10001,0b10001,0b10001], // 'H'
+    [0b01110,0b00100,0b00100,0b00100,0b00100,0b00100,0b01110], // 'I'
+    [0b00111,0b00010,0b00010,0b00010,0b00010,0b10010,0b01100], // 'J'
+    [0b10001,0b10010,0b10100,0b11000,0b10100,0b10010,0b10001], // 'K'
+    [0b10000,0b10000,0b10000,0b10000,0b10000,0b10000,0b11111], // 'L'
+    [0b10001,0b11011,0b10101,0b10101,0b10001,0b10001,0b10001], // 'M'
+    [0b10001,0b10001,0b11001,0b10101,0b10011,0b10001,0b10001], // 'N'
+    [0b01110,0b10001,0b10001,0b10001,0b10001,0b10001,0b01110], // 'O'
+    [0b11110,0b10001,0b10001,0b11110,0b10000,0b10000,0b10000], // 'P'
+    [0b01110,0b10001,0b10001,0b10001,0b10101,0b10010,0b01101], // 'Q'
+    [0b11110,0b10001,0b10001,0b11110,0b10100,0b10010,0b10001], // 'R'
+    [0b01111,0b10000,0b10000,0b01110,0b00001,0b00001,0b11110], // 'S'
+    [0b11111,0b00100,0b00100,0b00100,0b00100,0b00100,0b00100], // 'T'
+    [0b10001,0b10001,0b10001,0b10001,0b10001,0b10001,0b01110], // 'U'
+    [0b10001,0b10001,0b10001,0b10001,0b10001,0b01010,0b00100], // 'V'
+    [0b10001,0b10001,0b10001,0b10101,0b10101,0b10101,0b01010], // 'W'
+    [0b10001,0b10001,0b01010,0b00100,0b01010,0b10001,0b10001], // 'X'
+    [0b10001,0b10001,0b10001,0b01010,0b00100,0b00100,0b00100], // 'Y'
+    [0b11111,0b00001,0b00010,0b00100,0b01000,0b10000,0b11111], // 'Z'
+    [0b01110,0b01000,0b01000,0b01000,0b01000,0b01000,0b01110], // '['
+    [0b00000,0b10000,0b01000,0b00100,0b00010,0b00001,0b00000], // '\\'
+    [0b01110,0b00010,0b00010,0b00010,0b00010,0b00010,0b01110], // ']'
+    [0b00100,0b01010,0b10001,0b00000,0b00000,0b00000,0b00000], // '^'
+    [0b00000,0b00000,0b00000,0b00000,0b00000,0b00000,0b11111], // '_'
+    [0b01000,0b00100,0b00010,0b00000,0b00000,0b00000,0b00000], // '`'
+    [0b00000,0b00000,0b01110,0b00001,0b01111,0b10001,0b01111], // 'a'
+    [0b10000,0b10000,0b10110,0b11001,0b10001,0b10001,0b11110], // 'b'
+    [0b00000,0b00000,0b01110,0b10000,0b10000,0b10001,0b01110], // 'c'
+    [0b00001,0b00001,0b01101,0b10011,0b10001,0b10001,0b01111], // 'd'
+    [0b00000,0b00000,0b01110,0b10001,0b11111,0b10000,0b01110], // 'e'
+    [0b00110,0b01001,0b01000,0b11100,0b01000,0b01000,0b01000], // 'f'
+    [0b00000,0b01111,0b10001,0b10001,0b01111,0b00001,0b01110], // 'g'
+    [0b10000,0b10000,0b10110,0b11001,0b10001,0b10001,0b10001], // 'h'
+    [0b00100,0b00000,0b01100,0b00100,0b00100,0b00100,0b01110], // 'i'
+    [0b00010,0b00000,0b00110,0b00010,0b00010,0b10010,0b01100], // 'j'
+    [0b10000,0b10000,0b10010,0b10100,0b11000,0b10100,0b10010], // 'k'
+    [0b01100,0b00100,0b00100,0b00100,0b00100,0b00100,0b01110], // 'l'
+    [0b00000,0b00000,0b11010,0b10101,0b10101,0b10001,0b10001], // 'm'
+    [0b00000,0b00000,0b10110,0b11001,0b10001,0b10001,0b10001], // 'n'
+    [0b00000,0b00000,0b01110,0b10001,0b10001,0b10001,0b01110], // 'o'
+    [0b00000,0b00000,0b11110,0b10001,0b11110,0b10000,0b10000], // 'p'
+    [0b00000,0b00000,0b01101,0b10011,0b01111,0b00001,0b00001], // 'q'
+    [0b00000,0b00000,0b10110,0b11001,0b10000,0b10000,0b10000], // 'r'
+    [0b00000,0b00000,0b01110,0b10000,0b01110,0b00001,0b11110], // 's'
+    [0b01000,0b01000,0b11100,0b01000,0b01000,0b01001,0b00110], // 't'
+    [0b00000,0b00000,0b10001,0b10001,0b10001,0b10011,0b01101], // 'u'
+    [0b00000,0b00000,0b10001,0b10001,0b10001,0b01010,0b00100], // 'v'
+    [0b00000,0b00000,0b10001,0b10001,0b10101,0b10101,0b01010], // 'w'
+    [0b00000,0b00000,0b10001,0b01010,0b00100,0b01010,0b10001], // 'x'
+    [0b00000,0b00000,0b10001,0b10001,0b01111,0b00001,0b01110], // 'y'
+    [0b00000,0b00000,0b11111,0b00010,0b00100,0b01000,0b11111], // 'z'
+    [0b00010,0b00100,0b00100,0b01000,0b00100,0b00100,0b00010], // '{'
+    [0b00100,0b00100,0b00100,0b00100,0b00100,0b00100,0b00100], // '|'
+    [0b01000,0b00100,0b00100,0b00010,0b00100,0b00100,0b01000], // '}'
+    [0b00000,0b00000,0b01000,0b10101,0b00010,0b00000,0b00000], // '~'
+];
+
+/// Look up the 5x7 glyph for a printable ASCII character.
+/// Anything outside 0x20..=0x7E falls back to '?'.
+pub fn glyph_for(ch: char) -> [u8; SMALL_FONT_H] {
+    let code = ch as u32;
+    if (0x20..=0x7E).contains(&code) {
+        FONT_5X7[(code - 0x20) as usize]
+    } else {
+        FONT_5X7[(b'?' - 0x20) as usize]
+    }
+}
+
+pub fn draw_text_small(buf: &mut [u32], x: i32, y: i32, text: &str, color: u32) {
+    draw_text_small_scaled(buf, x, y, text, color, 1);
+}
+
+pub fn draw_text_small_scaled(buf: &mut [u32], x: i32, y: i32, text: &str, color: u32, scale: usize) {
+    let s = scale.max(1) as i32;
+    let mut cx = x;
+    for ch in text.chars() {
+        if ch != ' ' {
+            draw_glyph_small_scaled(buf, cx, y, &glyph_for(ch), color, s as usize);
+        }
+        cx += (SMALL_ADVANCE as i32) * s;
+    }
+}
+
+fn draw_glyph_small_scaled(buf: &mut [u32], x: i32, y: i32, glyph: &[u8; SMALL_FONT_H], color: u32, scale: usize) {
+    let s = scale.max(1) as i32;
+    for (row, bits) in glyph.iter().enumerate() {
+        for col in 0..SMALL_FONT_W {
+            // Bits are in lower 5 bits of the byte; MSB of 5 at position 4
+            let on = (bits >> (SMALL_FONT_W as u8 - 1 - col as u8)) & 1 == 1;
+            if on {
+                for dy in 0..s {
+                    for dx in 0..s {
+                        plot(x + col as i32 * s + dx, y + row as i32 * s + dy, color, buf);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pixel size (width, height) of a multi-line text block at the given scale.
+pub fn text_block_size(text: &str, scale: usize) -> (usize, usize) {
+    let s = scale.max(1);
+    let mut lines = 0usize;
+    let mut widest = 0usize;
+    for line in text.lines() {
+        lines += 1;
+        widest = widest.max(line.chars().count());
+    }
+    if lines == 0 { return (0, 0); }
+    // Trailing spacing is dropped so the block hugs its glyphs
+    let w = (widest * SMALL_ADVANCE).saturating_sub(SMALL_ADVANCE - SMALL_FONT_W) * s;
+    let h = (lines * SMALL_LINE_H).saturating_sub(SMALL_LINE_H - SMALL_FONT_H) * s;
+    (w, h)
+}
+
+/// Draw `text` line by line starting at (x, y); lines are separated by '\n'.
+pub fn draw_text_block(buf: &mut [u32], x: i32, y: i32, text: &str, color: u32, scale: usize) {
+    let s = scale.max(1) as i32;
+    for (i, line) in text.lines().enumerate() {
+        draw_text_small_scaled(buf, x, y + i as i32 * SMALL_LINE_H as i32 * s, line, color, s as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    // plot() draws into the 500x500 window buffer
+    const SIDE: usize = 500;
+
+    #[test]
+    fn every_printable_character_has_its_own_glyph() {
+        let glyphs: HashSet<[u8; SMALL_FONT_H]> = (0x21u8..=0x7E).map(|c| glyph_for(c as char)).collect();
+        assert_eq!(glyphs.len(), 94);
+        assert!(glyphs.iter().flatten().all(|&row| row < 1 << SMALL_FONT_W));
+        assert_eq!(glyph_for(' '), [0; SMALL_FONT_H]);
+        // Anything else is drawn as '?'
+        assert_eq!(glyph_for('é'), glyph_for('?'));
+        assert_eq!(glyph_for('\t'), glyph_for('?'));
+    }
+
+    #[test]
+    fn text_block_size_bounds_what_is_drawn() {
+        let text = "HUD\nMMMMMMMMMMM\n";
+        let (w, h) = text_block_size(text, 2);
+        assert_eq!((w, h), ((11 * SMALL_ADVANCE - 2) * 2, (2 * SMALL_LINE_H - 3) * 2));
+        assert_eq!(text_block_size("", 3), (0, 0));
+
+        let mut buf = vec![0u32; SIDE * SIDE];
+        draw_text_block(&mut buf, 20, 30, text, 1, 2);
+        let lit: Vec<(usize, usize)> = (0..SIDE * SIDE).filter(|&i| buf[i] == 1).map(|i| (i % SIDE, i / SIDE)).collect();
+        let (x0, x1) = (lit.iter().map(|p| p.0).min().unwrap(), lit.iter().map(|p| p.0).max().unwrap());
+        let (y0, y1) = (lit.iter().map(|p| p.1).min().unwrap(), lit.iter().map(|p| p.1).max().unwrap());
+        // M lights every edge of its glyph box, so the block is hugged exactly
+        assert_eq!((x0, y0), (20, 30));
+        assert_eq!((x1 + 1 - x0, y1 + 1 - y0), (w, h));
+    }
+}
@@ -0,0 +1,50 @@
+// ---------- GNU MP backend (`--features gmp`) ----------
+//
+// GMP's mpz through rug's `Integer`, which owns the FFI and the limb-size
+// details on every platform GMP supports. Values cross over as u64 digits,
+// least significant first.
+
+use std::fmt;
+
+use num_bigint::BigUint;
+use rug::integer::Order;
+use rug::{Assign, Integer};
+
+use crate::backend::Backend;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Gmp(Integer);
+
+impl fmt::Display for Gmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) }
+}
+
+impl Backend for Gmp {
+    fn from_biguint(n: &BigUint) -> Self {
+        let digits: Vec<u64> = n.iter_u64_digits().collect();
+        Gmp(Integer::from_digits(&digits, Order::Lsf))
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        let bytes: Vec<u8> = self.0.to_digits::<u64>(Order::Lsf).iter().flat_map(|d| d.to_le_bytes()).collect();
+        BigUint::from_bytes_le(&bytes)
+    }
+
+    fn copy_from(&mut self, other: &Self) { self.0.assign(&other.0) }
+
+    fn is_zero(&self) -> bool { self.0 == 0 }
+    fn is_one(&self) -> bool { self.0 == 1 }
+    fn is_odd(&self) -> bool { self.0.is_odd() }
+    fn bits(&self) -> u64 { self.0.significant_bits().into() }
+    fn trailing_zeros(&self) -> u64 { self.0.find_one(0).unwrap_or(0).into() }
+
+    fn shr_assign(&mut self, s: u64) {
+        // usize holds any shift that matters: more bits than memory gives zero
+        self.0 >>= usize::try_from(s).unwrap_or(usize::MAX);
+    }
+
+    fn triple_plus_one(&mut self) {
+        self.0 *= 3u32;
+        self.0 += 1u32;
+    }
+}
@@ -0,0 +1,184 @@
+// ---------- Minimal flat JSON objects for line-based protocols ----------
+//
+// Only what our own messages need: a single object per line whose values are
+// strings, numbers, booleans or null. Nested values are rejected. Big numbers
+// travel as strings so nothing is lost to f64.
+
+use std::collections::HashMap;
+
+pub struct Obj(HashMap<String, Option<String>>);
+
+impl Obj {
+    /// Raw value of `key`; None if missing or null
+    pub fn opt(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.as_deref())
+    }
+
+    pub fn str(&self, key: &str) -> Result<&str, String> {
+        self.opt(key).ok_or_else(|| format!("missing field '{key}'"))
+    }
+
+    pub fn u64(&self, key: &str) -> Result<u64, String> {
+        let v = self.str(key)?;
+        v.parse::<u64>().map_err(|_| format!("field '{key}' is not an unsigned integer: {v}"))
+    }
+}
+
+/// Quote and escape a string for embedding in JSON output
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn parse_object(line: &str) -> Result<Obj, String> {
+    let mut p = Parser { s: line.as_bytes(), i: 0 };
+    let mut map = HashMap::new();
+    p.ws();
+    p.expect(b'{')?;
+    p.ws();
+    if p.peek() == Some(b'}') {
+        p.i += 1;
+    } else {
+        loop {
+            p.ws();
+            let key = p.string()?;
+            p.ws();
+            p.expect(b':')?;
+            p.ws();
+            let val = p.value()?;
+            map.insert(key, val);
+            p.ws();
+            match p.next() {
+                Some(b',') => continue,
+                Some(b'}') => break,
+                _ => return Err(format!("expected ',' or '}}' at byte {}", p.i)),
+            }
+        }
+    }
+    p.ws();
+    if p.i != p.s.len() { return Err(format!("trailing data at byte {}", p.i)); }
+    Ok(Obj(map))
+}
+
+struct Parser<'a> { s: &'a [u8], i: usize }
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> { self.s.get(self.i).copied() }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek();
+        if c.is_some() { self.i += 1; }
+        c
+    }
+
+    fn ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) { self.i += 1; }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.next() == Some(c) { Ok(()) } else { Err(format!("expected '{}' at byte {}", c as char, self.i)) }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            match self.next() {
+                None => return Err("unterminated string".into()),
+                Some(b'"') => break,
+                Some(b'\\') => match self.next() {
+                    Some(b'"') => out.push(b'"'),
+                    Some(b'\\') => out.push(b'\\'),
+                    Some(b'/') => out.push(b'/'),
+                    Some(b'n') => out.push(b'\n'),
+                    Some(b'r') => out.push(b'\r'),
+                    Some(b't') => out.push(b'\t'),
+                    Some(b'b') => out.push(0x08),
+                    Some(b'f') => out.push(0x0c),
+                    Some(b'u') => {
+                        let hex = self.s.get(self.i..self.i + 4).ok_or("truncated \\u escape")?;
+                        let code = u32::from_str_radix(std::str::from_utf8(hex).map_err(|e| e.to_string())?, 16)
+                            .map_err(|e| e.to_string())?;
+                        self.i += 4;
+                        let c = char::from_u32(code).unwrap_or('\u{fffd}');
+                        out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    _ => return Err(format!("bad escape at byte {}", self.i)),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+        String::from_utf8(out).map_err(|e| e.to_string())
+    }
+
+    fn value(&mut self) -> Result<Option<String>, String> {
+        match self.peek() {
+            Some(b'"') => self.string().map(Some),
+            Some(b'{' | b'[') => Err(format!("nested values are not supported (byte {})", self.i)),
+            Some(_) => {
+                let start = self.i;
+                while let Some(c) = self.peek() {
+                    if matches!(c, b',' | b'}' | b' ' | b'\t' | b'\r' | b'\n') { break; }
+                    self.i += 1;
+                }
+                let raw = std::str::from_utf8(&self.s[start..self.i]).map_err(|e| e.to_string())?;
+                if raw.is_empty() { return Err(format!("empty value at byte {start}")); }
+                Ok(if raw == "null" { None } else { Some(raw.to_string()) })
+            }
+            None => Err("unexpected end of input".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flat_objects() {
+        let o = parse_object(" {\"a\": \"x\", \"n\":42 ,\"b\":true,\"z\":null, \"big\":\"123456789012345678901234567890\"}\n").unwrap();
+        assert_eq!(o.str("a"), Ok("x"));
+        assert_eq!(o.u64("n"), Ok(42));
+        assert_eq!(o.opt("b"), Some("true"));
+        assert_eq!(o.opt("z"), None);
+        assert_eq!(o.opt("missing"), None);
+        assert_eq!(o.str("big").unwrap().parse::<num_bigint::BigUint>().unwrap().bits(), 97);
+        assert!(o.u64("a").is_err());
+        assert!(o.str("z").unwrap_err().contains("missing field 'z'"));
+        assert!(parse_object("{}").unwrap().opt("a").is_none());
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        for s in ["plain", "quote \" and \\ backslash", "line\nbreak\ttab\r", "\u{1} control", "unicode é ✓"] {
+            let o = parse_object(&format!("{{\"k\":{}}}", quote(s))).unwrap();
+            assert_eq!(o.str("k"), Ok(s));
+        }
+        let o = parse_object(r#"{"k":"\u00e9\/\b\f"}"#).unwrap();
+        assert_eq!(o.str("k"), Ok("é/\u{8}\u{c}"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for bad in [
+            "", "[]", "{", "{\"a\":1", "{\"a\" 1}", "{\"a\":}", "{\"a\":1,}", "{a:1}",
+            "{\"a\":\"unterminated}", "{\"a\":{\"nested\":1}}", "{\"a\":[1]}", "{\"a\":1} trailing",
+            "{\"a\":\"\\x\"}", "{\"a\":\"\\u12\"}",
+        ] {
+            assert!(parse_object(bad).is_err(), "accepted {bad:?}");
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// ---------- Lockstep batch engine for sequential scans below 2^64 ----------
+//
+// Below 2^64 a start costs far less arithmetic than bookkeeping, so instead of
+// one BigUint orbit at a time `LaneEngine` keeps LANES orbits in flight in
+// structure-of-arrays form (u64 values, steps, peaks) and advances them all
+// together with a branch-free shortcut step the compiler can vectorize.
+//
+// A lane retires as soon as its orbit lands on a value whose result is already
+// known, which in practice happens right after it drops below its start: the
+// memo holds every finished start of this scan. Orbits that reach 1 first
+// retire there. Stopping time and peak combine exactly, so every start gets the
+// same Classified as `detect_outcome`. A lane whose next 3x+1 would not fit
+// in a u64 stops there and is finished by a scalar u128 walk; anything beyond
+// that, or a lane that runs unusually long, goes to Floyd.
+//
+// New starts come through a sieve on residues mod 2^SIEVE_BITS: classes that
+// drop below their start within SIEVE_BITS steps are resolved on the spot with
+// a few scalar steps and one memo lookup, and only the survivors take a lane.
+// Results are handed out strictly in start order.
+
+use std::collections::VecDeque;
+
+use num_bigint::BigUint;
+
+use crate::maps::{StepMap, detect_outcome_map};
+use crate::{Classified, Outcome};
+
+const LANES: usize = 64;
+// Lockstep steps between retirement passes
+const INNER: usize = 4;
+const SIEVE_BITS: u32 = 10;
+// Lanes this long are almost certainly not going to find their way to a memo
+// hit; Floyd also copes with orbits that never reach 1
+const MAX_LANE_STEPS: u64 = 1 << 20;
+// Largest x for which 3x+1 still fits in a lane
+const TRIPLE_LIMIT: u64 = (u64::MAX - 1) / 3;
+// Results buffered ahead of the oldest unfinished start
+const MAX_PENDING: usize = 1 << 14;
+const EMPTY: u32 = u32::MAX;
+pub const MEMO_ENTRY_BYTES: u64 = 4 + 16;
+
+pub struct LaneEngine {
+    // One slot per lane; a free lane holds x == 1 and is stepped like any other
+    x: [u64; LANES],
+    start: [u64; LANES],
+    steps: [u64; LANES],
+    peak: [u64; LANES],
+    busy: [bool; LANES],
+    in_flight: usize,
+
+    base: u64, // first start of the scan; the memo is indexed from here
+    next: u64, // next start to feed
+    end: u64,  // exclusive
+    memo_steps: Vec<u32>, // grown on demand up to memo_cap
+    memo_peak: Vec<u128>,
+    memo_cap: usize,
+    survivor: Vec<bool>, // per residue mod 2^SIEVE_BITS
+
+    emit: u64, // start of pending[0]
+    pending: VecDeque<Option<Classified>>,
+
+    sieved: u64,
+    memo_hits: u64,
+    fallbacks: u64,
+}
+
+/// Residue classes mod 2^k that are not known to drop below their start within
+/// k shortcut steps. After j ≤ k steps, 2^k m + r has become 3^a 2^(k-j) m + T^j(r),
+/// where a counts the odd steps, so the class drops once 3^a < 2^j.
+pub fn sieve(k: u32) -> Vec<bool> {
+    (0..1u64 << k)
+        .map(|r| {
+            let (mut v, mut pow3, mut pow2) = (r as u128, 1u128, 1u128);
+            for _ in 0..k {
+                if v & 1 == 1 { v = (3 * v + 1) >> 1; pow3 *= 3; } else { v /= 2; }
+                pow2 *= 2;
+                if pow3 < pow2 { return false; }
+            }
+            true
+        })
+        .collect()
+}
+
+impl LaneEngine {
+    /// Engine for the starts in [start, end), with a memo of at most `memo_bytes`
+    pub fn new(start: u64, end: u64, memo_bytes: u64) -> Self {
+        let memo_cap = (memo_bytes / MEMO_ENTRY_BYTES).min(end.saturating_sub(start)) as usize;
+        LaneEngine {
+            x: [1; LANES],
+            start: [0; LANES],
+            steps: [0; LANES],
+            peak: [0; LANES],
+            busy: [false; LANES],
+            in_flight: 0,
+            base: start,
+            next: start,
+            end,
+            memo_steps: Vec::new(),
+            memo_peak: Vec::new(),
+            memo_cap,
+            survivor: sieve(SIEVE_BITS),
+            emit: start,
+            pending: VecDeque::new(),
+            sieved: 0,
+            memo_hits: 0,
+            fallbacks: 0,
+        }
+    }
+
+    fn memo_get(&self, x: u128) -> Option<(u64, u128)> {
+        if x == 1 { return Some((0, 1)); }
+        let i = usize::try_from(x.checked_sub(self.base as u128)?).ok()?;
+        let s = *self.memo_steps.get(i)?;
+        (s != EMPTY).then(|| (s as u64, self.memo_peak[i]))
+    }
+
+    fn finish(&mut self, n: u64, c: Classified) {
+        let i = (n - self.base) as usize;
+        if i < self.memo_cap && let (Some(steps), Some(peak)) = (c.stopping_time, u128::try_from(&c.peak).ok()) && steps < EMPTY as u64 {
+            if i >= self.memo_steps.len() {
+                self.memo_steps.resize(i + 1, EMPTY);
+                self.memo_peak.resize(i + 1, 0);
+            }
+            self.memo_steps[i] = steps as u32;
+            self.memo_peak[i] = peak;
+        }
+        self.pending[(n - self.emit) as usize] = Some(c);
+    }
+
+    fn reaches_one(steps: u64, peak: u128) -> Classified {
+        Classified { outcome: Outcome::ReachesOne, stopping_time: Some(steps), peak: BigUint::from(peak) }
+    }
+
+    /// Exact scalar shortcut steps from `x`, with `steps` and `peak` so far, until
+    /// the orbit hits the memo or 1. None after `max_steps` steps without either,
+    /// or if 3x+1 would not fit in a u128.
+    fn walk(&self, mut x: u128, mut steps: u64, mut peak: u128, max_steps: u64) -> Option<Classified> {
+        for _ in 0..=max_steps {
+            if let Some((s, p)) = self.memo_get(x) { return Some(Self::reaches_one(steps + s, peak.max(p))); }
+            if x & 1 == 1 {
+                let up = x.checked_mul(3)?.checked_add(1)?;
+                peak = peak.max(up);
+                x = up >> 1;
+                steps += 2;
+            } else {
+                x >>= 1;
+                steps += 1;
+            }
+        }
+        None
+    }
+
+    /// Hand new starts to free lanes, resolving sieved ones directly
+    fn feed(&mut self) {
+        while self.next < self.end && self.in_flight < LANES && self.pending.len() < MAX_PENDING {
+            let n = self.next;
+            self.next += 1;
+            self.pending.push_back(None);
+            if !self.survivor[(n & ((1 << SIEVE_BITS) - 1)) as usize] && let Some(c) = self.walk(n as u128, 0, n as u128, SIEVE_BITS as u64) {
+                self.sieved += 1;
+                self.finish(n, c);
+                continue;
+            }
+            let lane = self.busy.iter().position(|b| !b).expect("a lane is free");
+            self.busy[lane] = true;
+            self.in_flight += 1;
+            (self.x[lane], self.start[lane], self.steps[lane], self.peak[lane]) = (n, n, 0, n);
+        }
+    }
+
+    /// INNER shortcut steps on every lane at once. Lanes sitting at 1 stay there,
+    /// and so does a lane whose 3x+1 would overflow.
+    fn step_all(&mut self) {
+        for _ in 0..INNER {
+            for i in 0..LANES {
+                // Selects are done with masks so the loop body has no branches
+                let x = self.x[i];
+                let odd = (x & 1).wrapping_neg();
+                let fits = ((x <= TRIPLE_LIMIT) as u64).wrapping_neg();
+                let moving = ((x != 1) as u64).wrapping_neg() & (fits | !odd);
+                let up = x.wrapping_add(x >> 1).wrapping_add(1); // (3x+1)/2 for odd x
+                let next = (up & odd) | ((x >> 1) & !odd);
+                self.x[i] = (next & moving) | (x & !moving);
+                self.steps[i] += (1 + (odd & 1)) & moving;
+                self.peak[i] = self.peak[i].max((up << 1) & odd & moving);
+            }
+        }
+    }
+
+    /// Retire every lane whose result is now known
+    fn retire(&mut self) {
+        for i in 0..LANES {
+            if !self.busy[i] { continue; }
+            let (n, x) = (self.start[i], self.x[i]);
+            let c = if let Some((s, p)) = self.memo_get(x as u128) {
+                if x != 1 { self.memo_hits += 1; }
+                Self::reaches_one(self.steps[i] + s, (self.peak[i] as u128).max(p))
+            } else if self.steps[i] > MAX_LANE_STEPS {
+                self.fallbacks += 1;
+                detect_outcome_map(&BigUint::from(n), StepMap::default())
+            } else if x & 1 == 1 && x > TRIPLE_LIMIT {
+                // Stuck at the top of the u64 range; carry on in u128, or with Floyd
+                let budget = MAX_LANE_STEPS - self.steps[i];
+                self.walk(x as u128, self.steps[i], self.peak[i] as u128, budget).unwrap_or_else(|| {
+                    self.fallbacks += 1;
+                    detect_outcome_map(&BigUint::from(n), StepMap::default())
+                })
+            } else {
+                continue;
+            };
+            self.finish(n, c);
+            self.busy[i] = false;
+            self.x[i] = 1;
+            self.in_flight -= 1;
+        }
+    }
+
+    /// Next start and its classification, in start order; None once the range is done
+    pub fn next_result(&mut self) -> Option<(u64, Classified)> {
+        loop {
+            if let Some(Some(_)) = self.pending.front() {
+                let c = self.pending.pop_front().flatten()?;
+                let n = self.emit;
+                self.emit += 1;
+                return Some((n, c));
+            }
+            if self.pending.is_empty() && self.next >= self.end { return None; }
+            self.feed();
+            if self.in_flight > 0 {
+                self.step_all();
+                self.retire();
+            }
+        }
+    }
+
+    /// One-line summary for the end-of-run report
+    pub fn report(&self) -> String {
+        let done = (self.emit - self.base).max(1) as f64;
+        format!(
+            "lane engine: {} starts, {:.1}% resolved by the sieve, {:.1}% retired on memo hits, {} handed to Floyd",
+            self.emit - self.base, 100.0 * self.sieved as f64 / done, 100.0 * self.memo_hits as f64 / done, self.fallbacks
+        )
+    }
+}
@@ -0,0 +1,163 @@
+// ---------- In-place stepping on a reusable limb buffer ----------
+//
+// `collatz_next` allocates a fresh BigUint per step, and Floyd takes three
+// steps per iteration. `LimbBuf` keeps the value as little-endian u64 limbs in
+// a Vec that is stepped in place: 3x+1 and the following right shift happen in
+// a single pass over the limbs, and the buffer only reallocates when the value
+// outgrows its capacity, which a few limbs of headroom make rare.
+//
+// The limbs are kept normalized (no zero high limb), so equality and ordering
+// are plain limb comparisons.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use num_bigint::BigUint;
+
+use crate::backend::Backend;
+use crate::maps::StepMap;
+
+// Spare limbs reserved up front; an orbit rarely climbs more than a few words
+const HEADROOM: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimbBuf {
+    limbs: Vec<u64>,
+}
+
+/// `lo >> s` with the low bits of `hi` shifted in; `s` in 0..64
+#[inline]
+fn shr_pair(lo: u64, hi: u64, s: u32) -> u64 {
+    if s == 0 { lo } else { (lo >> s) | (hi << (64 - s)) }
+}
+
+impl LimbBuf {
+    /// Overwrite with `n`, keeping the allocation
+    pub fn assign(&mut self, n: &BigUint) {
+        self.limbs.clear();
+        self.limbs.reserve(n.iter_u64_digits().len() + HEADROOM);
+        self.limbs.extend(n.iter_u64_digits());
+    }
+
+    /// x = (3x + 1) >> s for an odd x, where s is the number of trailing zeros
+    /// of 3x+1 capped at `max_shift`. Returns s.
+    fn triple_plus_one_shr(&mut self, max_shift: u64) -> u64 {
+        // The low limb of 3x+1 decides the shift; it is zero only when 64 or more
+        // bits would be stripped, which takes a separate shift afterwards
+        let low = self.limbs[0].wrapping_mul(3).wrapping_add(1);
+        if low == 0 {
+            self.triple_plus_one_shr_bits(0);
+            let s = self.trailing_zeros().min(max_shift);
+            self.shr_assign(s);
+            return s;
+        }
+        let s = (low.trailing_zeros() as u64).min(max_shift);
+        self.triple_plus_one_shr_bits(s as u32);
+        s
+    }
+
+    /// x = (3x + 1) >> s, s in 0..64, multiplying and shifting in the same pass
+    fn triple_plus_one_shr_bits(&mut self, s: u32) {
+        let n = self.limbs.len();
+        let mut carry = 1u64;
+        let mut prev = 0u64;
+        for i in 0..n {
+            let t = self.limbs[i] as u128 * 3 + carry as u128;
+            let p = t as u64;
+            carry = (t >> 64) as u64;
+            if i > 0 { self.limbs[i - 1] = shr_pair(prev, p, s); }
+            prev = p;
+        }
+        self.limbs[n - 1] = shr_pair(prev, carry, s);
+        let top = carry >> s;
+        if top != 0 { self.limbs.push(top); }
+        if self.limbs.last() == Some(&0) { self.limbs.pop(); }
+    }
+}
+
+impl Backend for LimbBuf {
+    fn from_biguint(n: &BigUint) -> Self {
+        let mut buf = LimbBuf { limbs: Vec::new() };
+        buf.assign(n);
+        buf
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        let mut bytes = Vec::with_capacity(self.limbs.len() * 8);
+        for l in &self.limbs { bytes.extend_from_slice(&l.to_le_bytes()); }
+        BigUint::from_bytes_le(&bytes)
+    }
+
+    fn copy_from(&mut self, other: &LimbBuf) {
+        self.limbs.clear();
+        self.limbs.extend_from_slice(&other.limbs);
+    }
+
+    fn is_zero(&self) -> bool { self.limbs.is_empty() }
+    fn is_one(&self) -> bool { self.limbs == [1] }
+    fn is_odd(&self) -> bool { self.limbs.first().is_some_and(|l| l & 1 == 1) }
+
+    fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() as u64 * 64 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    fn trailing_zeros(&self) -> u64 {
+        let mut tz = 0;
+        for &l in &self.limbs {
+            if l != 0 { return tz + l.trailing_zeros() as u64; }
+            tz += 64;
+        }
+        0
+    }
+
+    fn shr_assign(&mut self, s: u64) {
+        let (words, bits) = ((s / 64) as usize, (s % 64) as u32);
+        if words >= self.limbs.len() { self.limbs.clear(); return; }
+        if words > 0 { self.limbs.drain(..words); }
+        if bits > 0 {
+            let n = self.limbs.len();
+            for i in 0..n {
+                let hi = if i + 1 < n { self.limbs[i + 1] } else { 0 };
+                self.limbs[i] = shr_pair(self.limbs[i], hi, bits);
+            }
+        }
+        if self.limbs.last() == Some(&0) { self.limbs.pop(); }
+    }
+
+    fn triple_plus_one(&mut self) {
+        if self.limbs.is_empty() { self.limbs.push(1); } else { self.triple_plus_one_shr_bits(0); }
+    }
+
+    /// The fused version: 3x+1 and the shift that follows it in one pass
+    fn step(&mut self, map: StepMap) -> u64 {
+        let odd = self.is_odd();
+        match map {
+            StepMap::Standard if odd => { self.triple_plus_one_shr(0); 1 }
+            StepMap::Shortcut if odd => { self.triple_plus_one_shr(1); 2 }
+            StepMap::Syracuse if odd => 1 + self.triple_plus_one_shr(u64::MAX),
+            StepMap::Syracuse => {
+                let s = self.trailing_zeros();
+                self.shr_assign(s);
+                s
+            }
+            _ => { self.shr_assign(1); 1 }
+        }
+    }
+}
+
+impl fmt::Display for LimbBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.to_biguint().fmt(f) }
+}
+
+impl PartialOrd for LimbBuf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for LimbBuf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len()).then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
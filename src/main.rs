@@ -2,15 +2,48 @@ use std::env;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH, Duration, Instant};
-use std::collections::VecDeque;
-use std::sync::mpsc::{self, SyncSender, Receiver};
-use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread::{self, JoinHandle};
 
 use num_bigint::BigUint;
-use num_traits::One;
+use num_traits::{One, ToPrimitive, Zero};
 use num_integer::Integer;
-use minifb::{Window, WindowOptions, Key};
+
+mod backend;
+mod bench;
+mod cache;
+mod classify;
+mod codec;
+mod digest;
+mod dist;
+mod font;
+#[cfg(feature = "gmp")]
+mod gmp;
+mod json;
+mod lanes;
+mod limbs;
+mod maps;
+mod metrics;
+mod numexpr;
+mod orbit;
+mod progress;
+#[cfg(test)]
+mod props;
+mod rng;
+mod sampling;
+mod store;
+mod viz;
+use metrics::Metrics;
+use progress::{Progress, ProgressFormat, RunStats};
+use store::{Batch, Store};
+use cache::OrbitCache;
+use lanes::LaneEngine;
+use rng::{Rng, RngKind};
+use sampling::{Coverage, Sampler, Sampling};
+use maps::{StepMap, detect_outcome_map};
+use viz::{RunStatus, VizCmd, VizMsg, run_viz};
 
 /// Compute the next Collatz value for arbitrary-precision integers
 fn collatz_next(n: &BigUint) -> BigUint {
@@ -28,64 +61,162 @@ enum Outcome {
     StepsOverflow,       // exceeded u64::MAX steps while detecting
 }
 
-// Messages from compute thread to visualization thread
-enum VizMsg {
-    Draw(BigUint),
-    Stats { processed: u64, sps: f64 },
+// Result of classifying one start
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Classified {
+    outcome: Outcome,
+    stopping_time: Option<u64>, // steps until the first 1, when the orbit gets there
+    peak: BigUint,              // largest value on the orbit (max excursion)
 }
 
 /// Use Floyd's cycle-finding algorithm with O(1) memory to classify the orbit.
-fn detect_outcome(start: &BigUint) -> Outcome {
+/// For orbits that reach 1 the total stopping time (steps until the first 1) and
+/// the peak fall out of the tortoise's walk at no extra stepping cost. Steps with
+/// the default map (the shortcut map); results are the same under every map.
+fn detect_outcome(start: &BigUint) -> Classified {
+    detect_outcome_map(start, StepMap::default())
+}
+
+/// `detect_outcome` for an arbitrary map, so generalized maps with known nontrivial
+/// cycles (3x-1, 3x+5) can exercise every branch. Starts up to 4 are walked
+/// directly and must reach 1; stopping times assume orbits enter 1 via 4.
+/// It allocates a new BigUint per step; the search goes through the in-place
+/// `maps::detect_outcome_map`, and the bench keeps this one as its baseline.
+fn detect_outcome_with(start: &BigUint, next: impl Fn(&BigUint) -> BigUint) -> Classified {
+    // 1, 2 and 4 sit on the trivial cycle itself, where the meeting-point
+    // arithmetic below does not apply; just walk them. 0 is a fixed point that
+    // would never get there, so it is left to Floyd (callers reject it anyway)
+    if !start.is_zero() && *start <= BigUint::from(4u32) {
+        let mut x = start.clone();
+        let mut steps = 0u64;
+        let mut peak = start.clone();
+        while !x.is_one() {
+            x = next(&x);
+            steps += 1;
+            if x > peak { peak = x.clone(); }
+        }
+        return Classified { outcome: Outcome::ReachesOne, stopping_time: Some(steps), peak };
+    }
+
     // Advance one/two steps with overflow checks
     let mut step_count: u64 = 0;
 
-    let mut tortoise = collatz_next(start);
-    let mut hare = collatz_next(&collatz_next(&tortoise));
+    let mut tortoise = next(start);
+    let mut hare = next(&next(&tortoise));
+    // The tortoise visits every value up to the meeting point, which (see below)
+    // covers the whole orbit before 1
+    let mut peak = if tortoise > *start { tortoise.clone() } else { start.clone() };
 
     loop {
         if tortoise == hare { break; }
 
-        tortoise = collatz_next(&tortoise);
+        tortoise = next(&tortoise);
+        if tortoise > peak { peak = tortoise.clone(); }
         // hare moves two steps
-        hare = collatz_next(&collatz_next(&hare));
+        hare = next(&next(&hare));
 
         step_count = step_count.wrapping_add(1);
-        if step_count == u64::MAX { return Outcome::StepsOverflow; }
+        if step_count == u64::MAX { return Classified { outcome: Outcome::StepsOverflow, stopping_time: None, peak }; }
     }
 
-    // We have a cycle; determine whether it contains 1 (i.e., 1-4-2 loop)
+    // We have a cycle; determine whether it contains 1 (i.e., 1-4-2 loop).
+    // The tortoise is at index step_count + 1 and, for starts above 4, meets the
+    // hare no later than the first 1 (the orbit enters the cycle at 4), so the
+    // distance from the meeting point to 1 completes the stopping time.
     let meet = tortoise;
     let mut x = meet.clone();
+    let mut to_one: u64 = 0;
     loop {
-        if x == BigUint::one() { return Outcome::ReachesOne; }
-        x = collatz_next(&x);
+        if x.is_one() {
+            return Classified { outcome: Outcome::ReachesOne, stopping_time: Some(step_count + 1 + to_one), peak };
+        }
+        x = next(&x);
+        to_one += 1;
         if x == meet { break; }
     }
-    Outcome::NontrivialCycle
+    Classified { outcome: Outcome::NontrivialCycle, stopping_time: None, peak }
 }
 
-#[allow(clippy::type_complexity)]
-fn parse_args() -> (Option<BigUint>, Option<u64>, String, bool, bool, u64, u64) {
+// First start of sequential scans when none is given
+const DEFAULT_START: &str = "2^68";
+// Random mode samples uniformly from this inclusive range
+const RANDOM_RANGE: (&str, &str) = ("2^68", "2^2000-1");
+
+// Starts per journaled batch when --db is given
+const DB_BATCH: u64 = 10_000;
+// Memo for the lane engine when --cache-mb does not size it
+const LANE_MEMO_MB: u64 = 64;
+
+// Command-line options for the search
+struct Args {
+    start: Option<BigUint>,
+    count: Option<u64>,
+    solution: String,
+    checkpoint: String,
+    resume: bool,
+    random: bool,
+    viz: bool,
+    viz_interval: u64,
+    viz_max_steps: u64,
+    exit_when_done: bool,
+    progress_format: ProgressFormat,
+    progress_file: Option<String>,
+    progress_interval: u64,
+    metrics_addr: Option<String>,
+    db: Option<String>,
+    cache_mb: u64,
+    map: StepMap,
+    batch: bool,
+    rng: RngKind,
+    seed: Option<u64>,
+    sampling: Sampling,
+    strata_bits: u32,
+}
+
+fn parse_args() -> Result<Args, String> {
     let mut start: Option<BigUint> = None;
     let mut count: Option<u64> = None;
     let mut solution = String::from("solution.txt");
+    let mut checkpoint = String::from("collatz-checkpoint.txt");
+    let mut resume = false;
     let mut random = true; // default ON
     let mut viz = true;    // default ON
     let mut viz_interval: u64 = 1_000; // draw often by default
     let mut viz_max_steps: u64 = 10_000; // limit steps when rendering
+    let mut exit_when_done = false;
+    let mut progress_format = ProgressFormat::Text;
+    let mut progress_file: Option<String> = None;
+    let mut progress_interval: u64 = 5; // seconds between JSON records
+    let mut metrics_addr: Option<String> = None;
+    let mut db: Option<String> = None;
+    let mut cache_mb: u64 = 0; // 0 = no orbit cache
+    let mut map = StepMap::default();
+    let mut batch = true; // lane engine for sequential scans below 2^64
+    let mut rng = RngKind::default();
+    let mut seed: Option<u64> = None; // None = seed random mode from the clock
+    let mut sampling = Sampling::default();
+    let mut strata_bits: u32 = 10; // residue classes mod 2^10 for stratification and coverage
 
     let mut args = env::args().skip(1).peekable();
     while let Some(arg) = args.next() {
         match arg.as_str() {
             "--start" | "-s" => {
-                if let Some(v) = args.next() { start = v.parse::<BigUint>().ok(); }
+                let v = args.next().ok_or("--start needs a value")?;
+                start = Some(numexpr::parse_biguint(&v).map_err(|e| format!("--start: {e}"))?);
             }
             "--count" | "-n" => {
-                if let Some(v) = args.next() { count = v.parse::<u64>().ok(); }
+                let v = args.next().ok_or("--count needs a value")?;
+                count = Some(numexpr::parse_u64(&v).map_err(|e| format!("--count: {e}"))?);
             }
             "--solution" => {
                 if let Some(v) = args.next() { solution = v; }
             }
+            "--checkpoint" => {
+                if let Some(v) = args.next() { checkpoint = v; }
+            }
+            "--resume" => {
+                resume = true;
+            }
             "--random" => {
                 random = true;
             }
@@ -99,81 +230,293 @@ fn parse_args() -> (Option<BigUint>, Option<u64>, String, bool, bool, u64, u64)
                 viz = false;
             }
             "--viz-interval" => {
-                if let Some(v) = args.next() { if let Ok(n) = v.parse::<u64>() { viz_interval = n; } }
+                let v = args.next().ok_or("--viz-interval needs a value")?;
+                viz_interval = numexpr::parse_u64(&v).map_err(|e| format!("--viz-interval: {e}"))?;
             }
             "--viz-max-steps" => {
-                if let Some(v) = args.next() { if let Ok(n) = v.parse::<u64>() { viz_max_steps = n.max(100); } }
+                let v = args.next().ok_or("--viz-max-steps needs a value")?;
+                viz_max_steps = numexpr::parse_u64(&v).map_err(|e| format!("--viz-max-steps: {e}"))?.max(100);
             }
-            other => {
-                // Fallback positional handling: first number => start, second => count
-                if let Ok(v) = other.parse::<BigUint>() {
-                    if start.is_none() { start = Some(v); continue; }
+            "--exit-when-done" => {
+                exit_when_done = true;
+            }
+            "--progress-format" => {
+                if let Some(v) = args.next() {
+                    match ProgressFormat::parse(&v) {
+                        Some(f) => progress_format = f,
+                        None => eprintln!("warning: unknown progress format '{v}' (expected text or json)"),
+                    }
                 }
-                if let Ok(v) = other.parse::<u64>() {
-                    if count.is_none() { count = Some(v); continue; }
+            }
+            "--progress-file" => {
+                if let Some(v) = args.next() { progress_file = Some(v); }
+            }
+            "--progress-interval" => {
+                let v = args.next().ok_or("--progress-interval needs a value")?;
+                progress_interval = numexpr::parse_u64(&v).map_err(|e| format!("--progress-interval: {e}"))?.max(1);
+            }
+            "--metrics-addr" => {
+                if let Some(v) = args.next() { metrics_addr = Some(v); }
+            }
+            "--db" => {
+                if let Some(v) = args.next() { db = Some(v); }
+            }
+            "--cache-mb" => {
+                let v = args.next().ok_or("--cache-mb needs a value")?;
+                cache_mb = numexpr::parse_u64(&v).map_err(|e| format!("--cache-mb: {e}"))?;
+            }
+            "--batch" => {
+                batch = true;
+            }
+            "--no-batch" => {
+                batch = false;
+            }
+            "--map" => {
+                let v = args.next().ok_or("--map needs a value")?;
+                map = StepMap::parse(&v).ok_or_else(|| format!("unknown --map {v} (expected standard, shortcut or syracuse)"))?;
+            }
+            "--rng" => {
+                let v = args.next().ok_or("--rng needs a value")?;
+                rng = RngKind::parse(&v).ok_or_else(|| format!("unknown --rng {v} (expected xorshift, counter or chacha)"))?;
+            }
+            "--seed" => {
+                let v = args.next().ok_or("--seed needs a value")?;
+                seed = Some(numexpr::parse_u64(&v).map_err(|e| format!("--seed: {e}"))?);
+            }
+            "--sampling" => {
+                let v = args.next().ok_or("--sampling needs a value")?;
+                sampling = Sampling::parse(&v).ok_or_else(|| format!("unknown --sampling {v} (expected uniform, stratified or quasi)"))?;
+            }
+            "--strata-bits" => {
+                let v = args.next().ok_or("--strata-bits needs a value")?;
+                let k = numexpr::parse_u64(&v).map_err(|e| format!("--strata-bits: {e}"))?;
+                if !(1..=20).contains(&k) {
+                    return Err("--strata-bits: must be 1 to 20".into());
                 }
+                strata_bits = k as u32;
+            }
+            other => {
+                // Fallback positional handling: first number => start, second => count
+                if start.is_none() && let Ok(v) = numexpr::parse_biguint(other) { start = Some(v); continue; }
+                if count.is_none() && let Ok(v) = numexpr::parse_u64(other) { count = Some(v); continue; }
             }
         }
     }
 
-    (start, count, solution, random, viz, viz_interval, viz_max_steps)
+    Ok(Args {
+        start, count, solution, checkpoint, resume, random, viz, viz_interval, viz_max_steps, exit_when_done,
+        progress_format, progress_file, progress_interval, metrics_addr, db, cache_mb, map, batch, rng, seed,
+        sampling, strata_bits,
+    })
+}
+
+// Set by the SIGINT/SIGTERM handler; the search loop polls it between starts
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+fn install_signal_handler() {
+    let res = ctrlc::set_handler(|| {
+        if SHUTDOWN.swap(true, Ordering::SeqCst) {
+            // Second signal: the user really wants out
+            eprintln!("Forced exit.");
+            std::process::exit(130);
+        }
+        eprintln!("Stopping after the current start (signal again to force)...");
+    });
+    if let Err(e) = res {
+        eprintln!("warning: could not install signal handler: {e}");
+    }
 }
 
 fn real_main() -> Result<(), Box<dyn std::error::Error>> {
-    let (start_arg, count_arg, solution, random, viz, viz_interval_arg, viz_max_steps) = parse_args();
+    // Subcommands; anything else is the classic single-process search
+    match env::args().nth(1).as_deref() {
+        Some("coordinator") => return dist::coordinator_main(env::args().skip(2)),
+        Some("worker") => return dist::worker_main(env::args().skip(2)),
+        Some("verify-range") => return dist::verify_range_main(env::args().skip(2)),
+        Some("status") => return store::status_main(env::args().skip(2)),
+        Some("classify") => return classify::classify_main(env::args().skip(2)),
+        Some("orbit") => return orbit::orbit_main(env::args().skip(2)),
+        Some("library") => return orbit::library_main(env::args().skip(2)),
+        Some("bench") => return bench::bench_main(env::args().skip(2)),
+        _ => {}
+    }
 
-    // Determine start number. Default start is 2^68 when not provided explicitly.
-    let default_start: BigUint = BigUint::one() << 68; // 2^68
-    let start: BigUint = start_arg.unwrap_or(default_start);
+    let args = parse_args()?;
+    let mut random = args.random;
+    install_signal_handler();
+
+    // Determine start number. Default start is 2^68 when not provided explicitly;
+    // --resume picks up the checkpoint written by a previous sequential run.
+    let default_start = numexpr::parse_biguint(DEFAULT_START)?;
+    let resumed = if args.resume && args.start.is_none() { read_checkpoint(Path::new(&args.checkpoint))? } else { None };
+    if let Some(ref v) = resumed {
+        eprintln!("Resuming from checkpoint {}: {v}", args.checkpoint);
+    }
+    let start: BigUint = args.start.or(resumed).unwrap_or(default_start);
+    if start.is_zero() { return Err("--start: 0 has no Collatz orbit".into()); }
 
-    let count = count_arg; // None => run indefinitely
-    let viz_interval = viz_interval_arg.max(1);
-    let solution_path = Path::new(&solution);
+    let count = args.count; // None => run indefinitely
+    let mut viz_interval = args.viz_interval.max(1);
+    let viz_max_steps = args.viz_max_steps;
+    let solution_path = Path::new(&args.solution);
+    let checkpoint_path = Path::new(&args.checkpoint);
 
+    // The seed is printed so a random run can be replayed with --seed
+    let seed = args.seed.unwrap_or_else(rng::clock_seed);
     if random {
-        eprintln!("Random mode: sampling starts in [2^68, 2^2000-1]");
+        eprintln!(
+            "Random mode: {} sampling of starts in [{}, {}] ({} rng, seed {seed})",
+            args.sampling.name(), RANDOM_RANGE.0, RANDOM_RANGE.1, args.rng.name()
+        );
     } else {
         eprintln!("Starting sequential scan at {start}");
     }
 
     let mut processed: u64 = 0;
+    let mut progress = Progress::new(
+        args.progress_format,
+        args.progress_file.as_deref(),
+        Duration::from_secs(args.progress_interval),
+        count,
+    )?;
+    let mut stats = RunStats::new();
+
+    // Optional Prometheus endpoint
+    let metrics = match args.metrics_addr {
+        Some(ref addr) => {
+            let m = Metrics::new();
+            metrics::serve(addr, m.clone())?;
+            Some(m)
+        }
+        None => None,
+    };
+
+    // Optional results database; starts are journaled in batches so the file stays small
+    let mut db = match args.db {
+        Some(ref p) => Some(Store::open(Path::new(p))?),
+        None => None,
+    };
+    let mut batch = Batch::new();
+
+    // Sequential starts below 2^64 go through the lockstep lane engine, which keeps
+    // its own memo (sized by --cache-mb when given); --no-batch forces the scalar path
+    let mut lanes = match start.to_u64() {
+        Some(first) if args.batch && first > 0 => {
+            let end = count.and_then(|c| first.checked_add(c)).unwrap_or(u64::MAX);
+            let mb = if args.cache_mb > 0 { args.cache_mb } else { LANE_MEMO_MB };
+            Some(LaneEngine::new(first, end, mb << 20))
+        }
+        _ => None,
+    };
+
+    // Optional memo of stopping times for the scalar path; only sequential starts use it,
+    // random ones are too far apart to share orbits. Values up to the end of the scan get
+    // dense slots.
+    let mut cache = (args.cache_mb > 0 && lanes.is_none()).then(|| {
+        let top = count.and_then(|c| (&start + c).to_u64()).unwrap_or(u64::MAX);
+        OrbitCache::new(args.cache_mb << 20, top)
+    });
 
-    // Minimal PRNG (xorshift128+)
-    let mut rng = Rng::seeded();
+    let mut rng = Rng::new(args.rng, seed);
 
-    // Random range [2^68, 2^2000 - 1]
-    let rand_low: BigUint = BigUint::one() << 68;
-    let rand_high_inclusive: BigUint = (BigUint::one() << 2000) - BigUint::one();
+    let (rand_low, rand_high_inclusive) = random_range();
+    let mut sampler = Sampler::new(args.sampling, args.strata_bits, &rand_low, &rand_high_inclusive, &mut rng);
+    let mut coverage = Coverage::new(args.strata_bits, &rand_low, &rand_high_inclusive);
 
-    // Optional visualization thread/channel
-    let viz_sender: Option<SyncSender<VizMsg>> = if viz {
+    // Optional visualization thread with channels in both directions
+    let mut viz_handle: Option<JoinHandle<()>> = None;
+    let (viz_sender, viz_cmds): (Option<SyncSender<VizMsg>>, Option<Receiver<VizCmd>>) = if args.viz {
         let (tx, rx) = mpsc::sync_channel::<VizMsg>(4);
-        thread::spawn(move || run_viz(rx, viz_max_steps as usize));
-        Some(tx)
-    } else { None };
+        let (cmd_tx, cmd_rx) = mpsc::channel::<VizCmd>();
+        viz_handle = Some(thread::spawn(move || run_viz(rx, cmd_tx, viz_max_steps as usize, viz_interval)));
+        (Some(tx), Some(cmd_rx))
+    } else { (None, None) };
 
     let mut last_stat = Instant::now();
     let mut last_count: u64 = 0;
+    let mut last_sps: f64 = 0.0;
+    // Next start for sequential mode; kept separately so random/sequential can be toggled live
+    let mut seq_next: BigUint = start.clone();
+    let mut paused = false;
+    let mut force_draw = false;
+    let mut last_tested: Option<BigUint> = None;
+    let mut stop_reason = "count reached";
 
     loop {
-        let current: BigUint = if random {
-            rng.gen_range_biguint(&rand_low, &rand_high_inclusive)
+        if SHUTDOWN.load(Ordering::Relaxed) { stop_reason = "interrupted"; break; }
+
+        // Apply commands from the visualizer; while paused, wait for one (but keep watching for signals)
+        if let (Some(cmds), Some(tx)) = (&viz_cmds, &viz_sender) {
+            loop {
+                let cmd = if paused {
+                    match cmds.recv_timeout(Duration::from_millis(200)) {
+                        Ok(c) => c,
+                        Err(RecvTimeoutError::Timeout) if !SHUTDOWN.load(Ordering::Relaxed) => continue,
+                        Err(_) => { paused = false; break; }
+                    }
+                } else {
+                    match cmds.try_recv() { Ok(c) => c, Err(_) => break }
+                };
+                match cmd {
+                    VizCmd::TogglePause => {
+                        paused = !paused;
+                        eprintln!("{}", if paused { "Paused." } else { "Resumed." });
+                        // Restart the rate window so the pause does not drag samples/s down
+                        last_stat = Instant::now();
+                        last_count = processed;
+                    }
+                    VizCmd::DrawNext => force_draw = true,
+                    VizCmd::ToggleRandom => {
+                        // A sequential segment ends here; random samples never extend it
+                        if let Some(ref mut db) = db { db.flush(&mut batch)?; }
+                        random = !random;
+                        if random {
+                            eprintln!("Switched to random mode ({} rng, seed {seed})", args.rng.name());
+                        } else {
+                            eprintln!("Switched to sequential mode at {seq_next}");
+                        }
+                    }
+                    VizCmd::SetVizInterval(n) => viz_interval = n.max(1),
+                }
+                let _ = tx.try_send(VizMsg::Stats(RunStatus { processed, sps: last_sps, paused, random, viz_interval }));
+            }
+            if SHUTDOWN.load(Ordering::Relaxed) { stop_reason = "interrupted"; break; }
+        }
+
+        let (current, classified) = if random {
+            let v = sampler.next(&mut rng);
+            coverage.record(&v);
+            let c = detect_outcome_map(&v, args.map);
+            (v, c)
+        } else if let Some(ref mut e) = lanes && let Some((n, c)) = e.next_result() {
+            // The engine hands results out in start order, so this is always seq_next
+            seq_next = BigUint::from(n) + 1u32;
+            (BigUint::from(n), c)
         } else {
-            &start + &BigUint::from(processed)
+            let v = seq_next.clone();
+            seq_next += 1u32;
+            let c = match cache {
+                Some(ref mut c) => c.classify(&v),
+                None => detect_outcome_map(&v, args.map),
+            };
+            (v, c)
         };
-        let outcome = detect_outcome(&current);
+        let (outcome, stopping_time) = (classified.outcome, classified.stopping_time);
 
         // No progress writes in random or sequential modes
 
         // Send trajectory data at configured cadence
-        if let Some(ref tx) = viz_sender {
-            if processed % viz_interval == 0 {
-                let _ = tx.try_send(VizMsg::Draw(current.clone()));
-            }
+        if let Some(ref tx) = viz_sender && (force_draw || processed.is_multiple_of(viz_interval)) {
+            let _ = tx.try_send(VizMsg::Draw { start: current.clone(), outcome });
+            force_draw = false;
         }
 
-        if processed % 10000 == 0 {
-            eprintln!("Processed {processed} starts (up to {current})");
+        stats.record(outcome, stopping_time);
+        if let Some(ref m) = metrics { m.record(outcome, stopping_time, current.bits()); }
+        progress.tick(processed, &current)?;
+        if let Some(ref mut db) = db && outcome == Outcome::ReachesOne {
+            batch.record(&current, !random, &classified);
+            if batch.len() >= DB_BATCH { db.flush(&mut batch)?; }
         }
 
         match outcome {
@@ -183,54 +526,103 @@ fn real_main() -> Result<(), Box<dyn std::error::Error>> {
             Outcome::NontrivialCycle => {
                 eprintln!("Found nontrivial loop starting from {current}.");
                 write_solution(solution_path, &format!("NONTRIVIAL_CYCLE_START {current}"))?;
+                if let Some(ref mut db) = db { db.add_finding("NONTRIVIAL_CYCLE_START", &current)?; }
+                stop_reason = "nontrivial cycle found";
+                last_tested = Some(current);
                 break;
             }
             Outcome::StepsOverflow => {
                 let kind = "RUNAWAY_STEPS_OVERFLOW_START";
                 eprintln!("Detected runaway ({kind}). Start: {current}");
                 write_solution(solution_path, &format!("{kind} {current}"))?;
+                if let Some(ref mut db) = db { db.add_finding(kind, &current)?; }
+                stop_reason = "runaway detected";
+                last_tested = Some(current);
                 break;
             }
         }
 
         processed = processed.saturating_add(1);
-        // Send stats periodically (~500ms)
-        if let Some(ref tx) = viz_sender {
+        // Refresh samples/s periodically (~500ms) for the viz HUD and the metrics endpoint
+        if viz_sender.is_some() || metrics.is_some() {
             let now = Instant::now();
             let elapsed = now.duration_since(last_stat);
             if elapsed >= Duration::from_millis(500) {
                 let delta = processed.saturating_sub(last_count) as f64;
                 let secs = elapsed.as_secs_f64().max(1e-9);
                 let sps = delta / secs;
-                let _ = tx.try_send(VizMsg::Stats { processed, sps });
+                last_sps = sps;
+                if let Some(ref tx) = viz_sender {
+                    let _ = tx.try_send(VizMsg::Stats(RunStatus { processed, sps, paused, random, viz_interval }));
+                }
+                if let Some(ref m) = metrics { m.set_sps(sps); }
                 last_stat = now;
                 last_count = processed;
             }
         }
-        if let Some(limit) = count {
-            if processed >= limit { 
-                eprintln!("Finished processing {processed} numbers. Keeping visualization open...");
-                // Keep sending the last computed trajectory to keep viz alive
-                if let Some(ref tx) = viz_sender {
-                    let _ = tx.try_send(VizMsg::Draw(current.clone()));
-                }
-                break; 
+        if let Some(limit) = count && processed >= limit {
+            eprintln!("Finished processing {processed} numbers.");
+            // Make sure the final tested start ends up in the viz history
+            if let Some(ref tx) = viz_sender {
+                let _ = tx.try_send(VizMsg::Draw { start: current.clone(), outcome });
             }
+            last_tested = Some(current);
+            break;
         }
+        last_tested = Some(current);
     }
-    
-    // If visualization is enabled, wait for user to close the window
-    if viz_sender.is_some() {
-        eprintln!("Computation complete. Close the visualization window or press Ctrl+C to exit.");
-        // Keep the main thread alive so the visualization thread continues running
-        loop {
-            thread::sleep(Duration::from_millis(1000));
+
+    // Final report and checkpoint happen on every exit path, including Ctrl+C
+    progress.finish(&stats, processed, stop_reason, last_tested.as_ref())?;
+    if let Some(ref c) = cache { eprintln!("{}", c.report()); }
+    if let Some(ref e) = lanes && seq_next != start { eprintln!("{}", e.report()); }
+    if coverage.samples() > 0 { eprintln!("{}", coverage.report()); }
+    if let Some(ref mut db) = db {
+        db.flush(&mut batch)?;
+        eprintln!("results: {} updated", args.db.as_deref().unwrap_or_default());
+    }
+    // Only sequential progress can be resumed; random samples leave nothing to pick up from
+    if seq_next != start {
+        write_checkpoint(checkpoint_path, &seq_next)?;
+        eprintln!("checkpoint: next sequential start {seq_next} written to {}", args.checkpoint);
+    }
+
+    // If visualization is still open, wait for the user to close it unless asked not to
+    if let Some(handle) = viz_handle {
+        drop(viz_sender);
+        let interrupted = SHUTDOWN.load(Ordering::Relaxed);
+        if !args.exit_when_done && !interrupted && !handle.is_finished() {
+            eprintln!("Computation complete. Close the visualization window or press Ctrl+C to exit.");
+            while !handle.is_finished() && !SHUTDOWN.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
         }
     }
     
     Ok(())
 }
 
+/// The saved next start, or None if there is no checkpoint yet
+fn read_checkpoint(path: &Path) -> Result<Option<BigUint>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    numexpr::parse_biguint(text.trim()).map(Some).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn write_checkpoint(path: &Path, next: &BigUint) -> std::io::Result<()> {
+    // Same single-line format as solution.txt; the next start that has not been tested yet
+    let mut f = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+    writeln!(f, "{next}")?;
+    f.sync_all()?;
+    Ok(())
+}
+
 fn write_solution(path: &Path, line: &str) -> std::io::Result<()> {
     // Overwrite solution.txt with a single line describing the finding
     let mut f = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
@@ -241,340 +633,342 @@ fn write_solution(path: &Path, line: &str) -> std::io::Result<()> {
     Ok(())
 }
 
-// Simple xorshift128+ RNG for environments without external crates
-struct Rng { s0: u64, s1: u64 }
-
-impl Rng {
-    fn seeded() -> Self {
-        // Seed from current time; mix to avoid zeros
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
-        let nanos: u128 = now.as_nanos();
-        // Split into two 64-bit seeds and scramble
-        let s0 = (nanos as u64).wrapping_mul(0x9E3779B97F4A7C15);
-        let mut s1 = ((nanos >> 64) as u64).wrapping_mul(0xD1B54A32D192ED03);
-        if s0 == 0 && s1 == 0 { s1 = 1; }
-        Rng { s0, s1 }
-    }
-
-    fn next_u64(&mut self) -> u64 {
-        // xorshift128+
-        let mut s1 = self.s0;
-        let s0 = self.s1;
-        self.s0 = s0;
-        s1 ^= s1 << 23;
-        let s1_new = s1 ^ s0 ^ (s1 >> 18) ^ (s0 >> 5);
-        self.s1 = s1_new;
-        self.s1.wrapping_add(s0)
-    }
-
-    // removed unused next_u128()
-
-    fn gen_range_biguint(&mut self, low: &BigUint, high_inclusive: &BigUint) -> BigUint {
-        use std::cmp::Ordering;
-        if low >= high_inclusive { return low.clone(); }
-        let one = BigUint::one();
-        let span = high_inclusive - low + &one; // inclusive span
-        // Precompute span byte length
-        let span_bytes = span.to_bytes_be();
-        let len = span_bytes.len().max(1);
-        loop {
-            // Generate len random bytes
-            let mut buf = vec![0u8; len];
-            let mut i = 0usize;
-            while i < len {
-                let r = self.next_u64();
-                let chunk = r.to_be_bytes();
-                let take = usize::min(8, len - i);
-                buf[i..i+take].copy_from_slice(&chunk[..take]);
-                i += take;
-            }
-            let v = BigUint::from_bytes_be(&buf);
-            match v.cmp(&span) {
-                Ordering::Less => return low + v,
-                _ => continue, // reject and retry
-            }
-        }
-    }
+/// Range sampled by random mode, inclusive
+fn random_range() -> (BigUint, BigUint) {
+    let bound = |s| numexpr::parse_biguint(s).expect("RANDOM_RANGE is a valid expression");
+    (bound(RANDOM_RANGE.0), bound(RANDOM_RANGE.1))
 }
 
-// ---------- Visualization (minifb) ----------
-const VIZ_W: usize = 500;
-const VIZ_H: usize = 500;
-
-fn run_viz(rx: Receiver<VizMsg>, max_steps: usize) {
-    let mut window = match Window::new(
-        "Collatz Visualizer",
-        VIZ_W,
-        VIZ_H,
-        WindowOptions {
-            resize: false,
-            scale: minifb::Scale::X1,
-            ..WindowOptions::default()
-        },
-    ) {
-        Ok(w) => w,
-        Err(e) => {
-            eprintln!("viz error: {e}");
-            return;
-        }
-    };
-
-    let mut buffer = vec![0u32; VIZ_W * VIZ_H];
-    // Streaming animation state
-    let mut current_n: Option<BigUint> = None;
-    let mut current_label: Option<String> = None;
-    let mut bits_window: VecDeque<usize> = VecDeque::with_capacity(max_steps.max(1));
-    let max_points = max_steps.max(1);
-    let steps_per_tick: usize = (max_points / 60).clamp(1, 2000);
-    let one = BigUint::one();
-    // Local RNG for fallback samples to keep animation moving
-    let mut vrng = Rng::seeded();
-    let rand_low: BigUint = BigUint::one() << 68;
-    let rand_high_inclusive: BigUint = (BigUint::one() << 2000) - BigUint::one();
-    
-    // Initial clear
-    clear_buffer(&mut buffer, 0xFFFFFFFF);
-    draw_grid(&mut buffer, 50, 0xFFE0E0E0);
-    draw_axes(&mut buffer, 10, 0xFF000000);
-    window.set_title("Collatz Visualizer - waiting for samples...");
-    let _ = window.update_with_buffer(&buffer, VIZ_W, VIZ_H);
-
-    // no need to track last_draw now that we redraw only on new data
-
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let mut should_redraw = false;
-        
-        // Check for new messages
-        let mut had_new_draw = false;
-        while let Ok(msg) = rx.try_recv() {
-            match msg {
-                VizMsg::Draw(start) => {
-                    // Begin animating this trajectory from scratch
-                    current_label = Some(short_decimal(&start, 12, 12));
-                    current_n = Some(start);
-                    bits_window.clear();
-                    should_redraw = true;
-                    had_new_draw = true;
-                }
-                VizMsg::Stats { processed, sps } => {
-                    window.set_title(&format!("Collatz Visualizer  |  processed={processed}  |  {sps:.1} samples/s"));
-                }
-            }
-        }
+// log10(2) split in two: HI keeps 32 significant bits, so shift * HI is exact for
+// any shift below 2^21, and LO carries the rest
+const LOG10_2_HI: f64 = 0.3010299955494702;
+const LOG10_2_LO: f64 = 1.1451100898021838e-10;
 
-        // Incrementally extend trajectory for animation
-        if let Some(ref mut n) = current_n {
-            for _ in 0..steps_per_tick {
-                // Record current magnitude
-                bits_window.push_back(bit_len_biguint(n).max(1));
-                if bits_window.len() > max_points { bits_window.pop_front(); }
-                // Advance
-                if *n == one { break; }
-                *n = collatz_next(n);
-            }
-            // If we reached 1 and didn't receive a new start, pick a fallback sample
-            if *n == one && !had_new_draw {
-                *n = vrng.gen_range_biguint(&rand_low, &rand_high_inclusive);
-                // Update the on-screen label for the new start
-                current_label = Some(short_decimal(&*n, 12, 12));
-                bits_window.clear();
-            }
-            should_redraw = true;
-        }
-
-        // Only redraw when we have new data
-        if should_redraw && bits_window.len() >= 2 {
-            clear_buffer(&mut buffer, 0xFFFFFFFF);
-            draw_grid(&mut buffer, 50, 0xFFE0E0E0);
-            draw_axes(&mut buffer, 10, 0xFF000000);
-            
-            // Draw the visible window
-            let pad = 10usize;
-            let w = VIZ_W - 2*pad;
-            let h = VIZ_H - 2*pad;
-            let len = bits_window.len();
-            let max_bits = *bits_window.iter().max().unwrap_or(&1);
-            let mut prev = point_xy(0, bits_window[0], len, max_bits, w, h, pad);
-            for (i, bits) in bits_window.iter().enumerate().skip(1) {
-                let curr = point_xy(i, *bits, len, max_bits, w, h, pad);
-                draw_line(prev.0 as i32, prev.1 as i32, curr.0 as i32, curr.1 as i32, 0xFF000000, &mut buffer);
-                prev = curr;
-            }
-            
-            // Draw seed label bottom-left with heading
-            if let Some(ref lbl) = current_label {
-                let scale_num: i32 = 2; // make number larger
-                let num_h = (SMALL_FONT_H as i32) * scale_num;
-                let gap = 4;
-                let num_y = (VIZ_H as i32) - 10 - num_h;
-                let heading_y = (num_y - gap - SMALL_FONT_H as i32).max(0);
-                draw_text_small(&mut buffer, 12, heading_y, "NUMBER BEING TESTED:", 0xFF000000);
-                draw_text_small_scaled(&mut buffer, 12, num_y, lbl, 0xFF000000, scale_num as usize);
-            }
-
-            let _ = window.update_with_buffer(&buffer, VIZ_W, VIZ_H);
-        } else {
-            window.update();
-        }
-        
-        thread::sleep(Duration::from_millis(10));
+/// The first `head` and last `tail` digits of n around "...", or all of it when
+/// that would hardly be shorter. Neither end needs the full decimal conversion:
+/// the trailing digits are n mod 10^tail, the leading ones come from log10 n.
+fn short_decimal(n: &BigUint, head: usize, tail: usize) -> String {
+    // n < 2^bits, so this catches everything up to head + tail + 3 digits
+    if n.bits() as f64 * std::f64::consts::LOG10_2 < (head + tail + 4) as f64 {
+        let s = n.to_str_radix(10);
+        if s.len() <= head + tail + 3 { return s; }
     }
+    let trail = if tail == 0 { String::new() } else { (n % BigUint::from(10u32).pow(tail as u32)).to_string() };
+    format!("{}...{trail:0>tail$}", leading_digits(n, head))
 }
 
-fn clear_buffer(buf: &mut [u32], color: u32) {
-    for px in buf.iter_mut() { *px = color; }
+/// The first `head` digits of n, which has more than that. Up to 12 of them
+/// come from the fractional part of log10 n, taken from the top 64 bits; when
+/// that lands too close to a digit boundary to be trusted, or for longer heads,
+/// they are n / 10^(digits - head) instead.
+fn leading_digits(n: &BigUint, head: usize) -> BigUint {
+    let bits = n.bits();
+    if head <= 12 && bits < 1 << 21 {
+        // n = m * 2^(bits - 1) with m in [1, 2), m taken from the top 64 bits
+        let shift = bits.saturating_sub(64);
+        let m = (n >> shift).to_u64().unwrap_or(u64::MAX) as f64 / 2f64.powi((bits - shift - 1) as i32);
+        let e = (bits - 1) as f64;
+        let hi = e * LOG10_2_HI;
+        let frac = ((hi - hi.floor()) + e * LOG10_2_LO + m.log10()).fract();
+        let x = 10f64.powf(frac + head as f64 - 1.0);
+        let f = x.fract();
+        if f > 1e-3 && f < 1.0 - 1e-3 && x < 10f64.powi(head as i32) {
+            return BigUint::from(x as u64);
+        }
+    }
+    // 2^(bits-1) <= n < 2^bits puts the digit count at `lower` or one more
+    let lower = ((bits - 1) as f64 * std::f64::consts::LOG10_2) as u32 + 1;
+    let q = n / BigUint::from(10u32).pow(lower - head as u32);
+    if q >= BigUint::from(10u32).pow(head as u32) { q / 10u32 } else { q }
 }
 
-// streaming visualization no longer uses a precomputed trajectory function
-
-fn point_xy(i: usize, bits: usize, len: usize, max_bits: usize, w: usize, h: usize, pad: usize) -> (usize, usize) {
-    let x = pad + (i.saturating_mul(w.saturating_sub(1))) / (len.saturating_sub(1).max(1));
-    // y: top is 0; map higher bits to lower y (higher on screen)
-    let y = pad + (h.saturating_sub(1)).saturating_sub((bits.saturating_mul(h.saturating_sub(1))) / max_bits.max(1));
-    (x.min(VIZ_W.saturating_sub(1)), y.min(VIZ_H.saturating_sub(1)))
+fn main() {
+    if let Err(e) = real_main() {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
 }
 
-fn draw_line(x0: i32, y0: i32, x1: i32, y1: i32, color: u32, buffer: &mut [u32]) {
-    let mut x0 = x0; let mut y0 = y0;
-    let dx = (x1 - x0).abs();
-    let sx = if x0 < x1 { 1 } else { -1 };
-    let dy = -(y1 - y0).abs();
-    let sy = if y0 < y1 { 1 } else { -1 };
-    let mut err = dx + dy;
-    loop {
-        plot(x0, y0, color, buffer);
-        if x0 == x1 && y0 == y1 { break; }
-        let e2 = 2 * err;
-        if e2 >= dy { err += dy; x0 += sx; }
-        if e2 <= dx { err += dx; y0 += sy; }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::limbs::LimbBuf;
+    use crate::rng::RandomSource;
+
+    fn big(s: &str) -> BigUint { s.parse().unwrap() }
+
+    // Starts that set a new record total stopping time (OEIS A006877) with that time (A006878)
+    const DELAY_RECORDS: [(u64, u64); 44] = [
+        (1, 0), (2, 1), (3, 7), (6, 8), (7, 16), (9, 19), (18, 20), (25, 23), (27, 111), (54, 112),
+        (73, 115), (97, 118), (129, 121), (171, 124), (231, 127), (313, 130), (327, 143), (649, 144),
+        (703, 170), (871, 178), (1161, 181), (2223, 182), (2463, 208), (2919, 216), (3711, 237),
+        (6171, 261), (10971, 267), (13255, 275), (17647, 278), (23529, 281), (26623, 307),
+        (34239, 310), (35655, 323), (52527, 339), (77031, 350), (106239, 353), (142587, 374),
+        (156159, 382), (216367, 385), (230631, 442), (410011, 448), (511935, 469), (626331, 508),
+        (837799, 524),
+    ];
+
+    /// Reference: walk until 1, counting steps and tracking the peak
+    fn walk(start: &BigUint) -> (u64, BigUint) {
+        let (mut x, mut steps, mut peak) = (start.clone(), 0u64, start.clone());
+        while !x.is_one() {
+            x = collatz_next(&x);
+            steps += 1;
+            if x > peak { peak = x.clone(); }
+        }
+        (steps, peak)
     }
-}
 
-fn plot(x: i32, y: i32, color: u32, buffer: &mut [u32]) {
-    if x < 0 || y < 0 { return; }
-    let x = x as usize; let y = y as usize;
-    if x >= VIZ_W || y >= VIZ_H { return; }
-    buffer[y * VIZ_W + x] = color;
-}
+    #[test]
+    fn collatz_next_small_values() {
+        assert_eq!(collatz_next(&big("1")), big("4"));
+        assert_eq!(collatz_next(&big("2")), big("1"));
+        assert_eq!(collatz_next(&big("27")), big("82"));
+        assert_eq!(collatz_next(&big("82")), big("41"));
+    }
 
-fn bit_len_biguint(n: &BigUint) -> usize {
-    let bytes = n.to_bytes_be();
-    if bytes.is_empty() { return 0; }
-    let leading = bytes[0].leading_zeros() as usize;
-    let bit_len = bytes.len().saturating_mul(8).saturating_sub(leading);
-    bit_len.min(5000)
-}
+    #[test]
+    fn collatz_next_crosses_word_boundaries() {
+        // 2^64 - 1 is odd: 3(2^64 - 1) + 1 = 3 * 2^64 - 2
+        let max64 = big("18446744073709551615");
+        assert_eq!(collatz_next(&max64), (BigUint::from(3u32) << 64) - 2u32);
+        assert_eq!(collatz_next(&(BigUint::one() << 128)), BigUint::one() << 127);
+    }
 
-fn draw_grid(buf: &mut [u32], spacing: usize, color: u32) {
-    for x in (0..VIZ_W).step_by(spacing.max(1)) {
-        for y in 0..VIZ_H { buf[y * VIZ_W + x] = color; }
+    #[test]
+    fn golden_stopping_times_and_peaks() {
+        let c = detect_outcome(&big("27"));
+        assert_eq!(c.outcome, Outcome::ReachesOne);
+        assert_eq!(c.stopping_time, Some(111));
+        assert_eq!(c.peak, big("9232"));
+        assert_eq!(detect_outcome(&big("703")).peak, big("250504"));
+        assert_eq!(detect_outcome(&(BigUint::one() << 100)).stopping_time, Some(100));
     }
-    for y in (0..VIZ_H).step_by(spacing.max(1)) {
-        for x in 0..VIZ_W { buf[y * VIZ_W + x] = color; }
+
+    #[test]
+    fn known_delay_records() {
+        for (start, steps) in DELAY_RECORDS {
+            let c = detect_outcome(&BigUint::from(start));
+            assert_eq!(c.outcome, Outcome::ReachesOne, "start {start}");
+            assert_eq!(c.stopping_time, Some(steps), "start {start}");
+        }
     }
-}
 
-fn draw_axes(buf: &mut [u32], pad: usize, color: u32) {
-    let x0 = pad; let x1 = VIZ_W - pad;
-    let y0 = pad; let y1 = VIZ_H - pad;
-    for x in x0..=x1 { buf[y1 * VIZ_W + x] = color; }
-    for y in y0..=y1 { buf[y * VIZ_W + x0] = color; }
-}
+    #[test]
+    fn delay_records_are_records() {
+        // Every new maximum below 30,000 must be exactly the listed record starts
+        let mut best = None;
+        let mut found = Vec::new();
+        for n in 1u64..30_000 {
+            let t = detect_outcome(&BigUint::from(n)).stopping_time.unwrap();
+            if best.is_none_or(|b| t > b) {
+                best = Some(t);
+                found.push((n, t));
+            }
+        }
+        let expected: Vec<(u64, u64)> = DELAY_RECORDS.iter().copied().filter(|(n, _)| *n < 30_000).collect();
+        assert_eq!(found, expected);
+    }
 
-// ---------- Small bitmap text rendering for digits and '.' ----------
-const SMALL_FONT_W: usize = 5;
-const SMALL_FONT_H: usize = 7;
-
-fn draw_text_small(buf: &mut [u32], x: i32, y: i32, text: &str, color: u32) {
-    let mut cx = x;
-    for ch in text.chars() {
-        if let Some(g) = glyph_for(ch) {
-            draw_glyph_small(buf, cx, y, &g, color);
-            cx += (SMALL_FONT_W as i32) + 2; // 1px spacing
-        } else if ch == ' ' {
-            cx += (SMALL_FONT_W as i32) + 2;
+    #[test]
+    fn detect_outcome_matches_direct_walk() {
+        for n in (1u64..3000).chain([1 << 20, (1 << 40) + 1]) {
+            let n = BigUint::from(n);
+            let c = detect_outcome(&n);
+            let (steps, peak) = walk(&n);
+            assert_eq!(c.stopping_time, Some(steps), "start {n}");
+            assert_eq!(c.peak, peak, "start {n}");
         }
+        let beyond = (BigUint::one() << 128) + 1u32;
+        assert_eq!(detect_outcome(&beyond).stopping_time, Some(walk(&beyond).0));
     }
-}
 
-fn draw_glyph_small(buf: &mut [u32], x: i32, y: i32, glyph: &[u8; SMALL_FONT_H], color: u32) {
-    for (row, bits) in glyph.iter().enumerate() {
-        for col in 0..SMALL_FONT_W {
-            // Bits are in lower 5 bits of the byte; MSB of 5 at position 4
-            let on = (bits >> (SMALL_FONT_W as u8 - 1 - col as u8)) & 1 == 1;
-            if on { plot(x + col as i32, y + row as i32, color, buf); }
+    #[test]
+    fn generalized_maps_hit_nontrivial_cycle() {
+        // 3x-1 has the cycle 5 -> 14 -> 7 -> 20 -> 10 -> 5
+        let three_x_minus_one = |n: &BigUint| if n.is_even() { n >> 1 } else { n * 3u32 - 1u32 };
+        for start in [5u32, 7, 10, 14, 20] {
+            let c = detect_outcome_with(&BigUint::from(start), three_x_minus_one);
+            assert_eq!(c.outcome, Outcome::NontrivialCycle, "3x-1 start {start}");
+            assert_eq!(c.stopping_time, None);
         }
+        assert_eq!(detect_outcome_with(&BigUint::from(8u32), three_x_minus_one).outcome, Outcome::ReachesOne);
+
+        // 3x+5 has the cycle 19 -> 62 -> 31 -> 98 -> 49 -> 152 -> 76 -> 38 -> 19
+        let three_x_plus_five = |n: &BigUint| if n.is_even() { n >> 1 } else { n * 3u32 + 5u32 };
+        let c = detect_outcome_with(&BigUint::from(19u32), three_x_plus_five);
+        assert_eq!(c.outcome, Outcome::NontrivialCycle);
+        assert_eq!(c.peak, BigUint::from(152u32));
     }
-}
 
-fn draw_text_small_scaled(buf: &mut [u32], x: i32, y: i32, text: &str, color: u32, scale: usize) {
-    let s = scale.max(1) as i32;
-    let mut cx = x;
-    for ch in text.chars() {
-        if ch == ' ' { cx += (SMALL_FONT_W as i32 + 2) * s; continue; }
-        if let Some(g) = glyph_for(ch) {
-            draw_glyph_small_scaled(buf, cx, y, &g, color, s as usize);
-            cx += ((SMALL_FONT_W as i32) + 2) * s;
+    #[test]
+    fn gen_range_biguint_stays_in_bounds() {
+        let mut rng = Rng::from_seed(7);
+        let ranges = [
+            (big("0"), big("0")),
+            (big("5"), big("7")),
+            (big("255"), big("256")),
+            (BigUint::one() << 64, (BigUint::one() << 64) + 3u32),
+            (BigUint::one() << 68, (BigUint::one() << 2000) - 1u32),
+        ];
+        for (lo, hi) in &ranges {
+            for _ in 0..500 {
+                let v = rng.gen_range_biguint(lo, hi);
+                assert!(*lo <= v && v <= *hi, "{v} outside [{lo}, {hi}]");
+            }
+        }
+        // Small ranges hit every value
+        let mut seen = [false; 3];
+        for _ in 0..200 {
+            let v = rng.gen_range_biguint(&big("5"), &big("7"));
+            seen[(v - 5u32).to_usize().unwrap()] = true;
         }
+        assert_eq!(seen, [true; 3]);
+        // An empty or inverted range collapses to the low end
+        assert_eq!(rng.gen_range_biguint(&big("9"), &big("3")), big("9"));
+    }
+
+    /// Pearson's statistic for observed counts against expected ones
+    fn chi_square(observed: &[u64], expected: &[f64]) -> f64 {
+        observed.iter().zip(expected).map(|(&o, &e)| (o as f64 - e).powi(2) / e).sum()
     }
-}
 
-fn draw_glyph_small_scaled(buf: &mut [u32], x: i32, y: i32, glyph: &[u8; SMALL_FONT_H], color: u32, scale: usize) {
-    let s = scale.max(1) as i32;
-    for (row, bits) in glyph.iter().enumerate() {
-        for col in 0..SMALL_FONT_W {
-            let on = (bits >> (SMALL_FONT_W as u8 - 1 - col as u8)) & 1 == 1;
-            if on {
-                for dy in 0..s {
-                    for dx in 0..s {
-                        plot(x + col as i32 * s + dx, y + row as i32 * s + dy, color, buf);
+    #[test]
+    fn gen_range_biguint_is_uniform_over_residues() {
+        // Spans just above a power of two and just below one, where sampling
+        // whole bytes used to reject often or skew
+        let ranges = [
+            (big("0"), (BigUint::one() << 2000) - (BigUint::one() << 68)),
+            (BigUint::one() << 68, (BigUint::one() << 2000) - 1u32),
+            (big("3"), (BigUint::one() << 64) + 2u32),
+            (big("0"), BigUint::from(3u32) << 100),
+        ];
+        const N: u64 = 20_000;
+        for kind in RngKind::ALL {
+            let mut rng = Rng::new(kind, 48);
+            for (lo, hi) in &ranges {
+                for m in [7u32, 16, 30] {
+                    let mut counts = vec![0u64; m as usize];
+                    for _ in 0..N {
+                        counts[(rng.gen_range_biguint(lo, hi) % m).to_usize().unwrap()] += 1;
                     }
+                    // 0.1% critical values for m - 1 degrees of freedom
+                    let critical = match m { 7 => 22.46, 16 => 37.70, _ => 58.30 };
+                    let stat = chi_square(&counts, &vec![N as f64 / m as f64; m as usize]);
+                    assert!(stat < critical, "{} rng: chi-square {stat:.1} mod {m} over [{lo}, {hi}]", kind.name());
                 }
             }
         }
     }
-}
 
-fn glyph_for(ch: char) -> Option<[u8; SMALL_FONT_H]> {
-    match ch {
-        '0' => Some([0b01110,0b10001,0b10011,0b10101,0b11001,0b10001,0b01110]),
-        '1' => Some([0b00100,0b01100,0b00100,0b00100,0b00100,0b00100,0b01110]),
-        '2' => Some([0b01110,0b10001,0b00001,0b00010,0b00100,0b01000,0b11111]),
-        '3' => Some([0b01110,0b10001,0b00001,0b00110,0b00001,0b10001,0b01110]),
-        '4' => Some([0b00010,0b00110,0b01010,0b10010,0b11111,0b00010,0b00010]),
-        '5' => Some([0b11111,0b10000,0b11110,0b00001,0b00001,0b10001,0b01110]),
-        '6' => Some([0b00110,0b01000,0b10000,0b11110,0b10001,0b10001,0b01110]),
-        '7' => Some([0b11111,0b00001,0b00010,0b00100,0b01000,0b01000,0b01000]),
-        '8' => Some([0b01110,0b10001,0b10001,0b01110,0b10001,0b10001,0b01110]),
-        '9' => Some([0b01110,0b10001,0b10001,0b01111,0b00001,0b00010,0b11100]),
-        '.' => Some([0b00000,0b00000,0b00000,0b00000,0b00000,0b00100,0b00100]),
-        ':' => Some([0b00000,0b00100,0b00100,0b00000,0b00100,0b00100,0b00000]),
-        'N' => Some([0b10001,0b11001,0b10101,0b10011,0b10001,0b10001,0b00000]),
-        'U' => Some([0b10001,0b10001,0b10001,0b10001,0b10001,0b01110,0b00000]),
-        'M' => Some([0b10001,0b11011,0b10101,0b10101,0b10001,0b10001,0b00000]),
-        'B' => Some([0b11110,0b10001,0b10001,0b11110,0b10001,0b10001,0b11110]),
-        'E' => Some([0b11111,0b10000,0b10000,0b11110,0b10000,0b10000,0b11111]),
-        'R' => Some([0b11110,0b10001,0b10001,0b11110,0b10100,0b10010,0b10001]),
-        'I' => Some([0b01110,0b00100,0b00100,0b00100,0b00100,0b00100,0b01110]),
-        'G' => Some([0b01110,0b10001,0b10000,0b10111,0b10001,0b10001,0b01110]),
-        'T' => Some([0b11111,0b00100,0b00100,0b00100,0b00100,0b00100,0b00100]),
-        'S' => Some([0b01111,0b10000,0b10000,0b01110,0b00001,0b00001,0b11110]),
-        'D' => Some([0b11110,0b10001,0b10001,0b10001,0b10001,0b10001,0b11110]),
-        _ => None,
+    #[test]
+    fn gen_range_biguint_bit_lengths_halve() {
+        // Over [0, 2^b - 1] a value has b - j bits with probability 2^-(j+1)
+        let mut rng = Rng::from_seed(480);
+        const N: u64 = 20_000;
+        for b in [10u64, 64, 65, 2000] {
+            let hi = (BigUint::one() << b) - 1u32;
+            let mut counts = [0u64; 7]; // j = 0..5, then everything shorter
+            for _ in 0..N {
+                let j = (b - rng.gen_range_biguint(&big("0"), &hi).bits()).min(6);
+                counts[j as usize] += 1;
+            }
+            let mut expected: Vec<f64> = (0..6).map(|j| N as f64 / 2f64.powi(j + 1)).collect();
+            expected.push(N as f64 / 64.0);
+            let stat = chi_square(&counts, &expected);
+            assert!(stat < 22.46, "chi-square {stat:.1} on bit lengths below 2^{b}");
+        }
     }
-}
 
-fn short_decimal(n: &BigUint, head: usize, tail: usize) -> String {
-    let s = n.to_str_radix(10);
-    if s.len() <= head + tail + 3 { return s; }
-    let start = &s[..head.min(s.len())];
-    let end = &s[s.len()-tail.min(s.len())..];
-    format!("{}...{}", start, end)
-}
+    #[test]
+    fn rng_from_seed_is_reproducible() {
+        let (mut a, mut b) = (Rng::from_seed(42), Rng::from_seed(42));
+        for _ in 0..100 { assert_eq!(a.next_u64(), b.next_u64()); }
+        assert_ne!(Rng::from_seed(1).next_u64(), Rng::from_seed(2).next_u64());
+    }
 
-fn main() {
-    if let Err(e) = real_main() {
-        eprintln!("error: {e}");
-        std::process::exit(1);
+    #[test]
+    fn zero_start_does_not_hang() {
+        // 0 is its own fixed point; every detector must return, not walk toward 1
+        assert_eq!(detect_outcome(&BigUint::zero()).outcome, Outcome::NontrivialCycle);
+        assert_eq!(detect_outcome_with(&BigUint::zero(), collatz_next).outcome, Outcome::NontrivialCycle);
+        for map in StepMap::ALL {
+            assert_eq!(detect_outcome_map(&BigUint::zero(), map).outcome, Outcome::NontrivialCycle);
+        }
+    }
+
+    #[test]
+    fn short_decimal_abbreviates_long_numbers() {
+        assert_eq!(short_decimal(&big("12345"), 3, 3), "12345");
+        // Not worth abbreviating when the ellipsis would save nothing
+        assert_eq!(short_decimal(&big("123456789"), 3, 3), "123456789");
+        assert_eq!(short_decimal(&big("1234567890"), 3, 3), "123...890");
+        assert_eq!(short_decimal(&(BigUint::one() << 100), 4, 4), "1267...5376");
+    }
+
+    #[test]
+    fn short_decimal_matches_full_conversion() {
+        let naive = |n: &BigUint, head: usize, tail: usize| {
+            let s = n.to_str_radix(10);
+            if s.len() <= head + tail + 3 { s } else { format!("{}...{}", &s[..head], &s[s.len() - tail..]) }
+        };
+        let ten = BigUint::from(10u32);
+        let mut values = Vec::new();
+        // Digit boundaries, where a rounded logarithm would be off by one
+        for k in [1, 10, 20, 21, 22, 23, 30, 64, 300, 1000, 4000] {
+            let p = ten.pow(k);
+            values.extend([&p - 1u32, p.clone(), &p + 1u32, &p * 2u32 - 1u32, &p * 5u32]);
+        }
+        for k in [64u64, 100, 128, 1000, 6000, 100_000] {
+            values.push((BigUint::one() << k) - 1u32);
+            values.push(BigUint::one() << k);
+        }
+        let mut rng = Rng::from_seed(47);
+        for bits in [70u64, 200, 2000, 20_000, 100_000] {
+            for _ in 0..3 {
+                let top = BigUint::one() << bits;
+                values.push(rng.gen_range_biguint(&(&top >> 1u32), &top));
+            }
+        }
+        for n in &values {
+            for (head, tail) in [(3, 3), (12, 12), (20, 20), (1, 0)] {
+                assert_eq!(short_decimal(n, head, tail), naive(n, head, tail), "{head}/{tail} of a {}-bit value", n.bits());
+            }
+        }
+    }
+
+    #[test]
+    fn limb_buf_bits_match_biguint_bits() {
+        let bits = |n: &BigUint| LimbBuf::from_biguint(n).bits();
+        assert_eq!(bits(&big("0")), 0);
+        assert_eq!(bits(&big("1")), 1);
+        assert_eq!(bits(&big("255")), 8);
+        assert_eq!(bits(&big("256")), 9);
+        for k in [63u32, 64, 65, 127, 128, 2000] {
+            let n = BigUint::one() << k;
+            assert_eq!(bits(&n), n.bits());
+        }
+        // The visualizer plots straight from the backend, so nothing is capped any more
+        assert_eq!(bits(&(BigUint::one() << 6000)), 6001);
+    }
+
+    #[test]
+    fn limb_buf_steps_like_collatz_next() {
+        // Carries across limbs, 3x+1 with 64+ trailing zeros, and shrinking by a limb
+        let third = (BigUint::one() << 128u32) / 3u32; // 3 * third + 1 == 2^128
+        for n in [big("1"), big("27"), (BigUint::one() << 64) - 1u32, BigUint::one() << 64, third.clone()] {
+            let mut buf = LimbBuf::from_biguint(&n);
+            let mut x = n.clone();
+            for _ in 0..300 {
+                if x.is_one() { break; }
+                buf.step(StepMap::Standard);
+                x = collatz_next(&x);
+                assert_eq!(buf.to_biguint(), x, "from {n}");
+            }
+        }
+        let mut buf = LimbBuf::from_biguint(&third);
+        assert_eq!(buf.step(StepMap::Syracuse), 129);
+        assert!(buf.is_one());
     }
 }
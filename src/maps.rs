@@ -0,0 +1,144 @@
+// ---------- Shortcut and Syracuse maps ----------
+//
+// The standard map spends a separate iteration halving every 3x+1. Two
+// accelerated maps stride over the same orbit in fewer iterations:
+//   shortcut (Terras)  T(x) = (3x+1)/2 for odd x, x/2 for even x
+//   syracuse           S(x) = (3x+1)/2^v for odd x, x/2^v for even x,
+//                      where v strips every trailing zero bit at once
+// Each step also reports how many standard steps it covers, so stopping times
+// convert back exactly. The peak is the largest 3x+1 over the odd values of the
+// orbit (or the start), which all three maps see, so a classification under any
+// map is identical to the standard one; only the Floyd iteration count changes.
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::Zero;
+
+use crate::backend::{Backend, Num};
+use crate::{Classified, Outcome, collatz_next};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StepMap {
+    Standard,
+    #[default]
+    Shortcut,
+    Syracuse,
+}
+
+impl StepMap {
+    pub const ALL: [StepMap; 3] = [StepMap::Standard, StepMap::Shortcut, StepMap::Syracuse];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "standard" => Some(StepMap::Standard),
+            "shortcut" | "terras" => Some(StepMap::Shortcut),
+            "syracuse" | "odd" => Some(StepMap::Syracuse),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StepMap::Standard => "standard",
+            StepMap::Shortcut => "shortcut",
+            StepMap::Syracuse => "syracuse",
+        }
+    }
+
+    /// One step of this map: the next value and the number of standard steps it stands for
+    pub fn step(self, x: &BigUint) -> (BigUint, u64) {
+        match self {
+            StepMap::Standard => (collatz_next(x), 1),
+            StepMap::Shortcut => (shortcut_next(x), if x.is_odd() { 2 } else { 1 }),
+            StepMap::Syracuse => syracuse_next(x),
+        }
+    }
+}
+
+/// Terras map: (3x+1)/2 for odd x, x/2 for even x
+pub fn shortcut_next(x: &BigUint) -> BigUint {
+    if x.is_even() { x >> 1u32 } else { (x * 3u32 + 1u32) >> 1u32 }
+}
+
+/// Syracuse map with its standard step count: an odd x goes to the odd part of
+/// 3x+1, an even x to its own odd part. Trailing zeros are counted on the limbs
+/// and removed with a single shift.
+pub fn syracuse_next(x: &BigUint) -> (BigUint, u64) {
+    let (y, odd_step) = if x.is_odd() { (x * 3u32 + 1u32, 1) } else { (x.clone(), 0) };
+    let Some(v) = y.trailing_zeros() else { return (y, odd_step) };
+    (y >> v, odd_step + v)
+}
+
+/// Tracks the standard peak without materializing any 3x+1: the peak is
+/// 3m+1 for the largest odd value m stepped from (1 ends the orbit, so it does
+/// not count), or the start itself when that is larger
+struct PeakTracker<N> {
+    max_odd: N,
+}
+
+impl<N: Backend> PeakTracker<N> {
+    fn new() -> Self { PeakTracker { max_odd: N::from_biguint(&BigUint::ZERO) } }
+
+    fn note(&mut self, x: &N) {
+        if x.is_odd() && !x.is_one() && *x > self.max_odd { self.max_odd.copy_from(x); }
+    }
+
+    fn peak(&self, start: &BigUint) -> BigUint {
+        if self.max_odd.is_zero() { return start.clone(); }
+        start.clone().max(self.max_odd.to_biguint() * 3u32 + 1u32)
+    }
+}
+
+/// `detect_outcome` driven by any of the maps, stepping in place with the
+/// build's backend (see backend.rs)
+pub fn detect_outcome_map(start: &BigUint, map: StepMap) -> Classified {
+    detect_outcome_in::<Num>(start, map)
+}
+
+/// The detector on a given backend. Stopping time and peak are reported in standard terms. The argument
+/// that the tortoise meets the hare no later than the first 1 carries over: the
+/// trivial cycle is entered at 4, 2 or 1 respectively and 1 is its last element
+/// before re-entry.
+pub fn detect_outcome_in<N: Backend>(start: &BigUint, map: StepMap) -> Classified {
+    let mut peak = PeakTracker::<N>::new();
+    let mut tortoise = N::from_biguint(start);
+    // 0 maps to itself and never reaches 1; Floyd settles it as a cycle at once
+    if !start.is_zero() && *start <= BigUint::from(4u32) {
+        let mut steps = 0u64;
+        while !tortoise.is_one() {
+            peak.note(&tortoise);
+            steps += tortoise.step(map);
+        }
+        return Classified { outcome: Outcome::ReachesOne, stopping_time: Some(steps), peak: peak.peak(start) };
+    }
+
+    // Standard steps from the start to the tortoise
+    peak.note(&tortoise);
+    let mut steps = tortoise.step(map);
+    let mut hare = tortoise.clone();
+    hare.step(map);
+    hare.step(map);
+    let mut iterations: u64 = 0;
+    while tortoise != hare {
+        peak.note(&tortoise);
+        steps = steps.saturating_add(tortoise.step(map));
+        hare.step(map);
+        hare.step(map);
+        iterations += 1;
+        if iterations == u64::MAX {
+            return Classified { outcome: Outcome::StepsOverflow, stopping_time: None, peak: peak.peak(start) };
+        }
+    }
+
+    // The hare's buffer is free again; walk it from the meeting point
+    let meet = tortoise;
+    let mut x = hare;
+    loop {
+        if x.is_one() {
+            return Classified { outcome: Outcome::ReachesOne, stopping_time: Some(steps), peak: peak.peak(start) };
+        }
+        steps = steps.saturating_add(x.step(map));
+        if x == meet { break; }
+    }
+    Classified { outcome: Outcome::NontrivialCycle, stopping_time: None, peak: peak.peak(start) }
+}
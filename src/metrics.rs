@@ -0,0 +1,264 @@
+// ---------- Prometheus text-format metrics endpoint ----------
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::Outcome;
+use crate::dist::UnitResult;
+
+// Counters and gauges shared between the search loop (or a dist coordinator or
+// worker) and the HTTP threads. Plain atomics: the writers only ever do relaxed
+// stores/adds, the server reads.
+pub struct Metrics {
+    processed: AtomicU64,
+    sps_bits: AtomicU64, // f64 stored as raw bits
+    reaches_one: AtomicU64,
+    nontrivial_cycle: AtomicU64,
+    steps_overflow: AtomicU64,
+    frontier_bits: AtomicU64,
+    max_steps: AtomicU64,
+    units: AtomicU64, // dist work units completed
+    verified: AtomicU64,
+    mismatches: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Metrics {
+            processed: AtomicU64::new(0),
+            sps_bits: AtomicU64::new(0f64.to_bits()),
+            reaches_one: AtomicU64::new(0),
+            nontrivial_cycle: AtomicU64::new(0),
+            steps_overflow: AtomicU64::new(0),
+            frontier_bits: AtomicU64::new(0),
+            max_steps: AtomicU64::new(0),
+            units: AtomicU64::new(0),
+            verified: AtomicU64::new(0),
+            mismatches: AtomicU64::new(0),
+        })
+    }
+
+    pub fn record(&self, outcome: Outcome, stopping_time: Option<u64>, frontier_bits: u64) {
+        let counter = match outcome {
+            Outcome::ReachesOne => &self.reaches_one,
+            Outcome::NontrivialCycle => &self.nontrivial_cycle,
+            Outcome::StepsOverflow => &self.steps_overflow,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.processed.fetch_add(1, Ordering::Relaxed);
+        self.frontier_bits.store(frontier_bits, Ordering::Relaxed);
+        if let Some(t) = stopping_time { self.max_steps.fetch_max(t, Ordering::Relaxed); }
+    }
+
+    /// Fold in a finished dist unit's counts
+    pub fn record_unit(&self, res: &UnitResult) {
+        self.processed.fetch_add(res.processed, Ordering::Relaxed);
+        self.reaches_one.fetch_add(res.reaches_one, Ordering::Relaxed);
+        self.nontrivial_cycle.fetch_add(res.nontrivial_cycle, Ordering::Relaxed);
+        self.steps_overflow.fetch_add(res.steps_overflow, Ordering::Relaxed);
+        if res.max_steps_start.is_some() { self.max_steps.fetch_max(res.max_steps, Ordering::Relaxed); }
+        self.units.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a redundant copy whose digest was compared with the original's
+    pub fn record_check(&self, matched: bool) {
+        let counter = if matched { &self.verified } else { &self.mismatches };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_sps(&self, sps: f64) {
+        self.sps_bits.store(sps.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let get = |a: &AtomicU64| a.load(Ordering::Relaxed);
+        let sps = f64::from_bits(get(&self.sps_bits));
+        format!(
+            "# HELP collatz_starts_processed_total Starts fully classified since launch.\n\
+             # TYPE collatz_starts_processed_total counter\n\
+             collatz_starts_processed_total {}\n\
+             # HELP collatz_samples_per_second Recent throughput, same window as the visualizer stats.\n\
+             # TYPE collatz_samples_per_second gauge\n\
+             collatz_samples_per_second {sps}\n\
+             # HELP collatz_outcomes_total Starts by classification outcome.\n\
+             # TYPE collatz_outcomes_total counter\n\
+             collatz_outcomes_total{{outcome=\"reaches_one\"}} {}\n\
+             collatz_outcomes_total{{outcome=\"nontrivial_cycle\"}} {}\n\
+             collatz_outcomes_total{{outcome=\"steps_overflow\"}} {}\n\
+             # HELP collatz_frontier_bits Bit length of the most recently tested start.\n\
+             # TYPE collatz_frontier_bits gauge\n\
+             collatz_frontier_bits {}\n\
+             # HELP collatz_max_stopping_time_steps Largest total stopping time seen so far.\n\
+             # TYPE collatz_max_stopping_time_steps gauge\n\
+             collatz_max_stopping_time_steps {}\n\
+             # HELP collatz_units_completed_total Dist work units completed (coordinator: accepted, worker: computed).\n\
+             # TYPE collatz_units_completed_total counter\n\
+             collatz_units_completed_total {}\n\
+             # HELP collatz_units_verified_total Redundant copies whose digest matched the original.\n\
+             # TYPE collatz_units_verified_total counter\n\
+             collatz_units_verified_total {}\n\
+             # HELP collatz_digest_mismatches_total Redundant copies whose digest differed from the original.\n\
+             # TYPE collatz_digest_mismatches_total counter\n\
+             collatz_digest_mismatches_total {}\n",
+            get(&self.processed),
+            get(&self.reaches_one),
+            get(&self.nontrivial_cycle),
+            get(&self.steps_overflow),
+            get(&self.frontier_bits),
+            get(&self.max_steps),
+            get(&self.units),
+            get(&self.verified),
+            get(&self.mismatches),
+        )
+    }
+}
+
+/// Bind `addr` and serve `GET /metrics` from background threads, returning the bound address.
+/// Binding errors are returned so a typo in the address fails the run up front.
+pub fn serve(addr: &str, metrics: Arc<Metrics>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let bound = listener.local_addr()?;
+    eprintln!("Serving metrics on http://{bound}/metrics");
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            // One thread per connection, so a client that never sends its request
+            // only ties up its own thread until the read timeout
+            let metrics = metrics.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle(stream, &metrics) {
+                    eprintln!("metrics: {e}");
+                }
+            });
+        }
+    });
+    Ok(bound)
+}
+
+fn handle(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    // Only the request line matters; read until the end of headers or 8 KiB
+    let mut req = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !req.windows(4).any(|w| w == b"\r\n\r\n") && req.len() < 8192 {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 { break; }
+        req.extend_from_slice(&chunk[..n]);
+    }
+    let line = String::from_utf8_lossy(&req);
+    let mut parts = line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (status, ctype, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics.render()),
+        ("GET", "/") => ("200 OK", "text/plain", "collatz search metrics: see /metrics\n".to_string()),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {ctype}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check `text` against the Prometheus text exposition format: HELP and TYPE
+    /// once per family before its samples, valid names and labels, float values,
+    /// a trailing newline. Returns the samples as (name with labels, value).
+    fn parse_exposition(text: &str) -> Vec<(String, f64)> {
+        let valid_name = |s: &str| {
+            s.chars().enumerate().all(|(i, c)| c.is_ascii_alphabetic() || c == '_' || c == ':' || (i > 0 && c.is_ascii_digit()))
+                && !s.is_empty()
+        };
+        assert!(text.ends_with('\n'));
+        let (mut helped, mut typed, mut samples) = (Vec::new(), Vec::new(), Vec::new());
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let name = rest.split(' ').next().unwrap();
+                assert!(valid_name(name) && !helped.contains(&name.to_string()), "{line}");
+                helped.push(name.to_string());
+            } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert!(helped.last().is_some_and(|h| h == name), "TYPE before HELP: {line}");
+                assert!(["counter", "gauge"].contains(&kind), "{line}");
+                if kind == "counter" { assert!(name.ends_with("_total"), "{line}"); }
+                typed.push(name.to_string());
+            } else {
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                let name = series.split('{').next().unwrap();
+                assert_eq!(typed.last().map(String::as_str), Some(name), "sample outside its family: {line}");
+                if let Some(labels) = series.strip_prefix(name).filter(|l| !l.is_empty()) {
+                    let inner = labels.strip_prefix('{').and_then(|l| l.strip_suffix('}')).unwrap();
+                    for pair in inner.split(',') {
+                        let (k, v) = pair.split_once('=').unwrap();
+                        assert!(valid_name(k) && v.starts_with('"') && v.ends_with('"'), "{line}");
+                    }
+                }
+                samples.push((series.to_string(), value.parse::<f64>().unwrap()));
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn render_is_valid_exposition_text() {
+        let m = Metrics::new();
+        m.record(Outcome::ReachesOne, Some(111), 5);
+        m.record(Outcome::ReachesOne, Some(7), 6);
+        m.record(Outcome::NontrivialCycle, None, 7);
+        m.set_sps(1234.5);
+        let samples = parse_exposition(&m.render());
+        let value = |series: &str| samples.iter().find(|(s, _)| s == series).map(|&(_, v)| v);
+        assert_eq!(value("collatz_starts_processed_total"), Some(3.0));
+        assert_eq!(value("collatz_samples_per_second"), Some(1234.5));
+        assert_eq!(value("collatz_outcomes_total{outcome=\"reaches_one\"}"), Some(2.0));
+        assert_eq!(value("collatz_outcomes_total{outcome=\"nontrivial_cycle\"}"), Some(1.0));
+        assert_eq!(value("collatz_outcomes_total{outcome=\"steps_overflow\"}"), Some(0.0));
+        assert_eq!(value("collatz_frontier_bits"), Some(7.0));
+        assert_eq!(value("collatz_max_stopping_time_steps"), Some(111.0));
+        assert_eq!(value("collatz_units_completed_total"), Some(0.0));
+    }
+
+    #[test]
+    fn dist_units_and_checks_are_exposed() {
+        let m = Metrics::new();
+        let res = UnitResult { processed: 10, reaches_one: 9, steps_overflow: 1, max_steps: 111, max_steps_start: Some(27u32.into()), ..Default::default() };
+        m.record_unit(&res);
+        m.record_unit(&res);
+        m.record_check(true);
+        m.record_check(true);
+        m.record_check(false);
+        let samples = parse_exposition(&m.render());
+        let value = |series: &str| samples.iter().find(|(s, _)| s == series).map(|&(_, v)| v);
+        assert_eq!(value("collatz_starts_processed_total"), Some(20.0));
+        assert_eq!(value("collatz_outcomes_total{outcome=\"reaches_one\"}"), Some(18.0));
+        assert_eq!(value("collatz_outcomes_total{outcome=\"steps_overflow\"}"), Some(2.0));
+        assert_eq!(value("collatz_max_stopping_time_steps"), Some(111.0));
+        assert_eq!(value("collatz_units_completed_total"), Some(2.0));
+        assert_eq!(value("collatz_units_verified_total"), Some(2.0));
+        assert_eq!(value("collatz_digest_mismatches_total"), Some(1.0));
+    }
+
+    #[test]
+    fn an_idle_connection_does_not_block_a_scrape() {
+        let addr = serve("127.0.0.1:0", Metrics::new()).unwrap();
+        // Connects and never sends a request line
+        let _idle = TcpStream::connect(addr).unwrap();
+        let mut scrape = TcpStream::connect(addr).unwrap();
+        scrape.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        scrape.write_all(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let mut reply = String::new();
+        scrape.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{reply}");
+        assert!(reply.contains("\ncollatz_starts_processed_total 0\n"), "{reply}");
+    }
+}
@@ -0,0 +1,198 @@
+// ---------- Numeric expressions for starts, bounds and counts ----------
+//
+// Accepts plain decimal plus what is convenient to type for huge starts:
+//   0x1f / 0b1011 / 0o17 literals, `_` digit separators, scientific `10e30`,
+//   `+ - *`, `^` (right associative, binds tighter than `*`) and parentheses.
+// So `2^68`, `2^100-1`, `3*2^50+7` and `(2^64+1)*3` all work. Everything is
+// evaluated in BigUint; an intermediate negative value is an error.
+
+use num_bigint::BigUint;
+use num_traits::{CheckedSub, One, ToPrimitive};
+
+// Refuse to build numbers wider than this; a typo like 2^2^40 should fail, not eat RAM
+const MAX_BITS: u64 = 1 << 24;
+
+/// Evaluate an expression to an unsigned big integer
+pub fn parse_biguint(s: &str) -> Result<BigUint, String> {
+    let mut p = Parser { s: s.as_bytes(), i: 0 };
+    let v = p.expr().map_err(|e| format!("{e} in '{s}'"))?;
+    p.ws();
+    if p.i < p.s.len() {
+        return Err(format!("unexpected '{}' at column {} in '{s}'", p.s[p.i] as char, p.i + 1));
+    }
+    Ok(v)
+}
+
+/// Evaluate an expression that must fit in a u64 (counts, sizes, seeds)
+pub fn parse_u64(s: &str) -> Result<u64, String> {
+    parse_biguint(s)?.to_u64().ok_or_else(|| format!("'{s}' does not fit in 64 bits"))
+}
+
+struct Parser<'a> { s: &'a [u8], i: usize }
+
+impl Parser<'_> {
+    fn ws(&mut self) {
+        while self.s.get(self.i).is_some_and(|c| c.is_ascii_whitespace()) { self.i += 1; }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.ws();
+        self.s.get(self.i).copied()
+    }
+
+    fn col(&self) -> usize { self.i + 1 }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<BigUint, String> {
+        let mut acc = self.term()?;
+        while let Some(op @ (b'+' | b'-')) = self.peek() {
+            let at = self.col();
+            self.i += 1;
+            let rhs = self.term()?;
+            acc = if op == b'+' {
+                acc + rhs
+            } else {
+                acc.checked_sub(&rhs).ok_or_else(|| format!("negative result at column {at}"))?
+            };
+        }
+        Ok(acc)
+    }
+
+    // term := power ('*' power)*
+    fn term(&mut self) -> Result<BigUint, String> {
+        let mut acc = self.power()?;
+        while self.peek() == Some(b'*') {
+            let at = self.col();
+            self.i += 1;
+            let rhs = self.power()?;
+            if acc.bits() + rhs.bits() > MAX_BITS { return Err(format!("product too large at column {at}")); }
+            acc *= rhs;
+        }
+        Ok(acc)
+    }
+
+    // power := atom ('^' power)?
+    fn power(&mut self) -> Result<BigUint, String> {
+        let base = self.atom()?;
+        if self.peek() != Some(b'^') { return Ok(base); }
+        let at = self.col();
+        self.i += 1;
+        let exp = self.power()?;
+        pow_checked(base, &exp).ok_or_else(|| format!("power too large at column {at}"))
+    }
+
+    // atom := literal | '(' expr ')'
+    fn atom(&mut self) -> Result<BigUint, String> {
+        match self.peek() {
+            Some(b'(') => {
+                self.i += 1;
+                let v = self.expr()?;
+                if self.peek() != Some(b')') { return Err(format!("expected ')' at column {}", self.col())); }
+                self.i += 1;
+                Ok(v)
+            }
+            Some(c) if c.is_ascii_digit() => self.literal(),
+            Some(c) => Err(format!("expected a number at column {}, found '{}'", self.col(), c as char)),
+            None => Err("expected a number at end of input".into()),
+        }
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<BigUint, String> {
+        let at = self.col();
+        let start = self.i;
+        while self.s.get(self.i).is_some_and(|c| *c == b'_' || (*c as char).is_digit(radix)) { self.i += 1; }
+        let text: Vec<u8> = self.s[start..self.i].iter().copied().filter(|c| *c != b'_').collect();
+        if text.is_empty() { return Err(format!("missing digits at column {at}")); }
+        // A letter glued to the digits (0x1g, 12abc) is a typo, not the end of the number
+        if let Some(c) = self.s.get(self.i) && c.is_ascii_alphanumeric() && !(radix == 10 && matches!(c, b'e' | b'E')) {
+            return Err(format!("invalid digit '{}' at column {}", *c as char, self.col()));
+        }
+        BigUint::parse_bytes(&text, radix).ok_or_else(|| format!("invalid number at column {at}"))
+    }
+
+    fn literal(&mut self) -> Result<BigUint, String> {
+        let radix = match (self.s.get(self.i), self.s.get(self.i + 1).map(|c| c.to_ascii_lowercase())) {
+            (Some(b'0'), Some(b'x')) => 16,
+            (Some(b'0'), Some(b'b')) => 2,
+            (Some(b'0'), Some(b'o')) => 8,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.i += 2;
+            return self.digits(radix);
+        }
+        let mantissa = self.digits(10)?;
+        if !matches!(self.s.get(self.i), Some(b'e' | b'E')) { return Ok(mantissa); }
+        // Scientific notation: 10e30 means 10 * 10^30 (integers only)
+        let at = self.col();
+        self.i += 1;
+        let exp = self.digits(10)?;
+        let scale = pow_checked(BigUint::from(10u32), &exp).ok_or_else(|| format!("exponent too large at column {at}"))?;
+        Ok(mantissa * scale)
+    }
+}
+
+/// base^exp, or None when the result would exceed MAX_BITS
+fn pow_checked(base: BigUint, exp: &BigUint) -> Option<BigUint> {
+    if base <= BigUint::one() || exp.bits() == 0 {
+        return Some(if exp.bits() == 0 { BigUint::one() } else { base });
+    }
+    let exp = exp.to_u64()?;
+    // bits(base^exp) <= exp * bits(base)
+    if exp.checked_mul(base.bits())? > MAX_BITS { return None; }
+    Some(base.pow(exp as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str) -> String { parse_biguint(s).unwrap().to_string() }
+
+    #[test]
+    fn advertised_forms_evaluate() {
+        assert_eq!(eval("3*2^50+7"), "3377699720527879");
+        assert_eq!(eval("10e30"), format!("1{}", "0".repeat(31)));
+        assert_eq!(eval("2^68-1"), "295147905179352825855");
+        assert_eq!(eval("(2^64+1)*3"), "55340232221128654851");
+        assert_eq!(eval("0x1f + 0b1011 + 0o17"), "57");
+        assert_eq!(eval("1_000_000"), "1000000");
+        assert_eq!(parse_u64("2^64-1"), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("2+3*4"), "14");
+        assert_eq!(eval("2*3^2"), "18");
+        assert_eq!(eval("(2+3)*4"), "20");
+        assert_eq!(eval("10-3-2"), "5", "minus is left associative");
+        assert_eq!(eval("2^3^2"), "512", "power is right associative");
+        assert_eq!(eval("2^0"), "1");
+        assert_eq!(eval("0^5"), "0");
+    }
+
+    #[test]
+    fn sizes_are_capped_at_max_bits() {
+        // The bound is checked as exp * bits(base), so 2^k is allowed up to k = MAX_BITS / 2
+        assert_eq!(parse_biguint("2^8388608").unwrap().bits(), MAX_BITS / 2 + 1);
+        assert_eq!(parse_biguint("2^8388609"), Err("power too large at column 2 in '2^8388609'".into()));
+        assert_eq!(parse_biguint("2^2^40"), Err("power too large at column 2 in '2^2^40'".into()));
+        assert_eq!(parse_biguint("2^8388608*2^8388608"), Err("product too large at column 10 in '2^8388608*2^8388608'".into()));
+        assert_eq!(parse_biguint("1e16777217"), Err("exponent too large at column 2 in '1e16777217'".into()));
+    }
+
+    #[test]
+    fn errors_name_the_problem_and_where_it_is() {
+        let err = |s: &str| parse_biguint(s).unwrap_err();
+        assert_eq!(err(""), "expected a number at end of input in ''");
+        assert_eq!(err("*3"), "expected a number at column 1, found '*' in '*3'");
+        assert_eq!(err("12abc"), "invalid digit 'a' at column 3 in '12abc'");
+        assert_eq!(err("0x1g"), "invalid digit 'g' at column 4 in '0x1g'");
+        assert_eq!(err("0x"), "missing digits at column 3 in '0x'");
+        assert_eq!(err("10e"), "missing digits at column 4 in '10e'");
+        assert_eq!(err("1-2"), "negative result at column 2 in '1-2'");
+        assert_eq!(err("(1+2"), "expected ')' at column 5 in '(1+2'");
+        assert_eq!(err("1 2"), "unexpected '2' at column 3 in '1 2'");
+        assert_eq!(parse_u64("2^64"), Err("'2^64' does not fit in 64 bits".into()));
+    }
+}
@@ -0,0 +1,307 @@
+// ---------- Orbit export: CSV, JSON Lines or compact binary ----------
+//
+// `orbit <START>` walks one trajectory until it reaches 1 (or --max-steps) and
+// writes it for analysis elsewhere. Text formats carry one row per selected
+// step: step index, value, bit length and parity. The binary format stores the
+// full orbit as one parity-bit record (see codec.rs), which determines every
+// value, followed by the per-step bit lengths as delta varints:
+//
+//   magic    "CLZORB1\n"
+//   record   start, steps, flags and parity bits as in codec.rs
+//   varints  steps + 1 zigzag-encoded bit-length deltas, the first relative to 0
+//
+// `library` builds and reads many such records in one indexed file.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::codec::{EncodedOrbit, LibraryReader, build_library, write_varint};
+use crate::{collatz_next, short_decimal};
+use crate::numexpr::{parse_biguint, parse_u64};
+
+type BoxError = Box<dyn std::error::Error>;
+
+pub const ORBIT_MAGIC: &[u8; 8] = b"CLZORB1\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format { Csv, Jsonl, Bin }
+
+fn zigzag(v: i64) -> u64 { ((v << 1) ^ (v >> 63)) as u64 }
+
+/// Collect which steps to emit in the text formats
+struct Select { every: u64, odd_only: bool }
+
+impl Select {
+    fn wants(&self, step: u64, value: &BigUint) -> bool {
+        step.is_multiple_of(self.every) && (!self.odd_only || value.is_odd())
+    }
+}
+
+fn write_row(out: &mut impl Write, format: Format, step: u64, value: &BigUint) -> io::Result<()> {
+    let parity = u8::from(value.is_odd());
+    match format {
+        Format::Csv => writeln!(out, "{step},{value},{},{parity}", value.bits()),
+        Format::Jsonl => writeln!(out, "{{\"step\":{step},\"value\":\"{value}\",\"bits\":{},\"parity\":{parity}}}", value.bits()),
+        Format::Bin => unreachable!("binary orbits are written whole"),
+    }
+}
+
+/// Walk the orbit; returns (steps, reached_one)
+fn export_text(out: &mut impl Write, format: Format, start: &BigUint, sel: &Select, max_steps: u64) -> io::Result<(u64, bool)> {
+    if format == Format::Csv { writeln!(out, "step,value,bits,parity")?; }
+    let mut x = start.clone();
+    let mut step = 0u64;
+    loop {
+        if sel.wants(step, &x) { write_row(out, format, step, &x)?; }
+        if x.is_one() { return Ok((step, true)); }
+        if step == max_steps { return Ok((step, false)); }
+        x = collatz_next(&x);
+        step += 1;
+    }
+}
+
+fn export_bin(out: &mut impl Write, start: &BigUint, max_steps: u64) -> Result<(u64, bool), BoxError> {
+    let orbit = EncodedOrbit::encode(start, max_steps);
+    out.write_all(ORBIT_MAGIC)?;
+    orbit.write_to(out)?;
+    let mut prev = 0i64;
+    for x in orbit.replay() {
+        let b = x?.bits() as i64;
+        write_varint(out, zigzag(b - prev))?;
+        prev = b;
+    }
+    Ok((orbit.steps, orbit.reached_one))
+}
+
+/// `orbit <START> [--format csv|jsonl|bin] [--every K] [--odd-only] [--max-steps N] [--output PATH]`
+pub fn orbit_main(mut args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let mut start: Option<BigUint> = None;
+    let mut format = Format::Csv;
+    let mut sel = Select { every: 1, odd_only: false };
+    let mut max_steps: u64 = 10_000_000;
+    let mut output: Option<String> = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--format" => {
+                format = match value()?.as_str() {
+                    "csv" => Format::Csv,
+                    "jsonl" | "json" => Format::Jsonl,
+                    "bin" => Format::Bin,
+                    other => return Err(format!("unknown --format {other} (expected csv, jsonl or bin)").into()),
+                }
+            }
+            "--every" => sel.every = parse_u64(&value()?).map_err(|e| format!("--every: {e}"))?.max(1),
+            "--odd-only" => sel.odd_only = true,
+            "--max-steps" => max_steps = parse_u64(&value()?).map_err(|e| format!("--max-steps: {e}"))?,
+            "--output" | "-o" => output = Some(value()?),
+            "--start" | "-s" => start = Some(parse_biguint(&value()?).map_err(|e| format!("--start: {e}"))?),
+            other if start.is_none() && !other.starts_with("--") => {
+                start = Some(parse_biguint(other).map_err(|e| format!("start: {e}"))?)
+            }
+            other => return Err(format!("unknown orbit option: {other}").into()),
+        }
+    }
+    let start = start.ok_or("usage: orbit <START> [--format csv|jsonl|bin] [--every K] [--odd-only] [--max-steps N] [--output PATH]")?;
+    if start.is_zero() { return Err("0 has no Collatz orbit".into()); }
+    if format == Format::Bin && (sel.every != 1 || sel.odd_only) {
+        return Err("--every and --odd-only apply to csv/jsonl; the binary format always holds the whole orbit".into());
+    }
+
+    let mut out: Box<dyn Write> = match output {
+        Some(ref p) => Box::new(BufWriter::new(File::create(p).map_err(|e| format!("{p}: {e}"))?)),
+        None if format == Format::Bin => return Err("--format bin needs --output <PATH>".into()),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    let (steps, reached) = match format {
+        Format::Bin => export_bin(&mut out, &start, max_steps)?,
+        _ => export_text(&mut out, format, &start, &sel, max_steps)?,
+    };
+    out.flush()?;
+    if reached {
+        eprintln!("orbit of {} reached 1 after {steps} steps", short_decimal(&start, 20, 20));
+    } else {
+        eprintln!("orbit stopped at --max-steps {steps} before reaching 1 (output is truncated)");
+    }
+    Ok(())
+}
+
+/// `library build --input <PATH|-> --output <LIB>`, `library info <LIB>`,
+/// `library get <LIB> <INDEX> [--format csv|jsonl] [--every K] [--odd-only]`
+pub fn library_main(mut args: impl Iterator<Item = String>) -> Result<(), BoxError> {
+    let usage = "usage: library build --input <PATH|-> --output <LIB> [--max-steps N] | library info <LIB> | library get <LIB> <INDEX> [--format csv|jsonl] [--every K] [--odd-only]";
+    match args.next().as_deref() {
+        Some("build") => {
+            let (mut input, mut output, mut max_steps) = (String::from("-"), None, 10_000_000u64);
+            while let Some(arg) = args.next() {
+                let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+                match arg.as_str() {
+                    "--input" | "-i" => input = value()?,
+                    "--output" | "-o" => output = Some(value()?),
+                    "--max-steps" => max_steps = parse_u64(&value()?).map_err(|e| format!("--max-steps: {e}"))?,
+                    other => return Err(format!("unknown library option: {other}").into()),
+                }
+            }
+            let output = output.ok_or("library build needs --output <LIB>")?;
+            let reader: Box<dyn BufRead> = if input == "-" {
+                Box::new(BufReader::new(io::stdin()))
+            } else {
+                Box::new(BufReader::new(File::open(&input).map_err(|e| format!("{input}: {e}"))?))
+            };
+            let n = build_library(reader, Path::new(&output), max_steps)?;
+            eprintln!("wrote {n} orbits to {output}");
+        }
+        Some("info") => {
+            let path = args.next().ok_or(usage)?;
+            let mut lib = LibraryReader::open(Path::new(&path)).map_err(|e| format!("{path}: {e}"))?;
+            let (mut steps, mut truncated) = (0u64, 0u64);
+            for i in 0..lib.len() {
+                let o = lib.get(i)?;
+                steps += o.steps;
+                if !o.reached_one { truncated += 1; }
+            }
+            let bytes = std::fs::metadata(&path)?.len();
+            println!("{path}: {} orbits, {steps} steps in {bytes} bytes, {truncated} truncated", lib.len());
+        }
+        Some("get") => {
+            let path = args.next().ok_or(usage)?;
+            let index = parse_u64(&args.next().ok_or(usage)?).map_err(|e| format!("index: {e}"))?;
+            let mut format = Format::Csv;
+            let mut sel = Select { every: 1, odd_only: false };
+            while let Some(arg) = args.next() {
+                let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+                match arg.as_str() {
+                    "--format" => {
+                        format = match value()?.as_str() {
+                            "csv" => Format::Csv,
+                            "jsonl" | "json" => Format::Jsonl,
+                            other => return Err(format!("unknown --format {other} (expected csv or jsonl)").into()),
+                        }
+                    }
+                    "--every" => sel.every = parse_u64(&value()?).map_err(|e| format!("--every: {e}"))?.max(1),
+                    "--odd-only" => sel.odd_only = true,
+                    other => return Err(format!("unknown library option: {other}").into()),
+                }
+            }
+            let mut lib = LibraryReader::open(Path::new(&path)).map_err(|e| format!("{path}: {e}"))?;
+            let orbit = lib.get(index).map_err(|e| format!("{path} #{index}: {e}"))?;
+            let mut out = BufWriter::new(io::stdout());
+            if format == Format::Csv { writeln!(out, "step,value,bits,parity")?; }
+            for (step, x) in orbit.replay().enumerate() {
+                let x = x?;
+                if sel.wants(step as u64, &x) { write_row(&mut out, format, step as u64, &x)?; }
+            }
+            out.flush()?;
+        }
+        _ => return Err(usage.into()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    const ALL: Select = Select { every: 1, odd_only: false };
+
+    type Row = (u64, BigUint, u64, u8);
+
+    /// Every value of the orbit of 27 as (step, value, bits, parity), walked directly
+    fn orbit_of_27() -> Vec<Row> {
+        let mut x = BigUint::from(27u32);
+        let mut rows = Vec::new();
+        loop {
+            rows.push((rows.len() as u64, x.clone(), x.bits(), u8::from(x.is_odd())));
+            if x.is_one() { return rows; }
+            x = collatz_next(&x);
+        }
+    }
+
+    fn text(format: Format, sel: &Select) -> String {
+        let mut out = Vec::new();
+        assert_eq!(export_text(&mut out, format, &BigUint::from(27u32), sel, u64::MAX).unwrap(), (111, true));
+        String::from_utf8(out).unwrap()
+    }
+
+    fn read_csv(text: &str) -> Vec<Row> {
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("step,value,bits,parity"));
+        lines
+            .map(|l| {
+                let f: Vec<&str> = l.split(',').collect();
+                (f[0].parse().unwrap(), f[1].parse().unwrap(), f[2].parse().unwrap(), f[3].parse().unwrap())
+            })
+            .collect()
+    }
+
+    fn take<'a>(r: &mut &'a [u8], n: usize) -> &'a [u8] {
+        let (head, rest) = r.split_at(n);
+        *r = rest;
+        head
+    }
+
+    fn varint(r: &mut &[u8]) -> u64 {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = take(r, 1)[0];
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 { break; }
+        }
+        v
+    }
+
+    #[test]
+    fn csv_and_jsonl_read_back_as_the_orbit_of_27() {
+        let orbit = orbit_of_27();
+        assert_eq!(orbit.len(), 112);
+        assert_eq!(read_csv(&text(Format::Csv, &ALL)), orbit);
+        let jsonl: Vec<Row> = text(Format::Jsonl, &ALL)
+            .lines()
+            .map(|l| {
+                let o = json::parse_object(l).unwrap();
+                let parity = o.u64("parity").unwrap() as u8;
+                (o.u64("step").unwrap(), o.str("value").unwrap().parse().unwrap(), o.u64("bits").unwrap(), parity)
+            })
+            .collect();
+        assert_eq!(jsonl, orbit);
+
+        let sel = Select { every: 10, odd_only: true };
+        let picked: Vec<Row> = orbit.into_iter().filter(|r| r.0 % 10 == 0 && r.3 == 1).collect();
+        assert_eq!(read_csv(&text(Format::Csv, &sel)), picked);
+    }
+
+    #[test]
+    fn binary_orbit_of_27_decodes_to_the_same_steps() {
+        let orbit = orbit_of_27();
+        let mut out = Vec::new();
+        assert_eq!(export_bin(&mut out, &BigUint::from(27u32), u64::MAX).unwrap(), (111, true));
+
+        let mut r = out.strip_prefix(ORBIT_MAGIC.as_slice()).unwrap();
+        let len = varint(&mut r) as usize;
+        assert_eq!(BigUint::from_bytes_le(take(&mut r, len)), BigUint::from(27u32));
+        assert_eq!(varint(&mut r), 111);
+        assert_eq!(take(&mut r, 1), [0], "not truncated");
+        let parity = take(&mut r, 111usize.div_ceil(8));
+        let mut bits = 0i64;
+        for (step, _, want_bits, want_parity) in &orbit {
+            let i = *step as usize;
+            if i < 111 { assert_eq!(parity[i / 8] >> (i % 8) & 1, *want_parity, "parity of step {i}"); }
+            let z = varint(&mut r);
+            bits += (z >> 1) as i64 ^ -((z & 1) as i64);
+            assert_eq!(bits as u64, *want_bits, "bits of step {i}");
+        }
+        assert!(r.is_empty());
+
+        // Cut short by --max-steps: the flag is set and only those steps are stored
+        let mut out = Vec::new();
+        assert_eq!(export_bin(&mut out, &BigUint::from(27u32), 20).unwrap(), (20, false));
+        let mut r = &out[ORBIT_MAGIC.len() + 2..];
+        assert_eq!((varint(&mut r), take(&mut r, 1)), (20, [1].as_slice()));
+    }
+}
@@ -0,0 +1,319 @@
+// ---------- Progress records and end-of-run summary ----------
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use num_bigint::BigUint;
+
+use crate::Outcome;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    Text, // the classic "Processed N starts" line every 10,000 starts
+    Json, // one JSON object per line, emitted on a time interval
+}
+
+impl ProgressFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(ProgressFormat::Text),
+            "json" => Some(ProgressFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Streaming histogram of total stopping times with 4 sub-buckets per power of two,
+// so memory stays fixed no matter how long the run is
+const STOP_BUCKETS: usize = 4 + 62 * 4;
+
+pub struct StoppingTimes {
+    counts: Vec<u64>,
+    n: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl StoppingTimes {
+    fn new() -> Self {
+        StoppingTimes { counts: vec![0; STOP_BUCKETS], n: 0, sum: 0, min: u64::MAX, max: 0 }
+    }
+
+    fn bucket_of(v: u64) -> usize {
+        if v < 4 { return v as usize; }
+        let e = 63 - v.leading_zeros() as usize; // >= 2
+        let sub = ((v >> (e - 2)) & 3) as usize;
+        4 + (e - 2) * 4 + sub
+    }
+
+    /// Half-open value range [lo, hi) covered by a bucket; the top bucket's hi
+    /// saturates at u64::MAX, which that bucket also holds
+    fn bucket_range(idx: usize) -> (u64, u64) {
+        if idx < 4 { return (idx as u64, idx as u64 + 1); }
+        let e = (idx - 4) / 4 + 2;
+        let sub = ((idx - 4) % 4) as u64;
+        let width = 1u64 << (e - 2);
+        ((4 + sub) * width, (5 + sub).saturating_mul(width))
+    }
+
+    pub fn record(&mut self, v: u64) {
+        self.counts[Self::bucket_of(v)] += 1;
+        self.n += 1;
+        self.sum += v as u128;
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.n == 0 { 0.0 } else { self.sum as f64 / self.n as f64 }
+    }
+
+    /// Estimated `p`th percentile (0 to 100): the rank's bucket is found exactly and
+    /// the value placed evenly inside it, so the error is under a quarter of the value
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        if self.n == 0 { return None; }
+        let rank = ((p / 100.0 * self.n as f64).ceil() as u64).clamp(1, self.n);
+        let mut below = 0;
+        for (lo, hi, c) in self.buckets() {
+            if below + c >= rank {
+                let k = (rank - below) as f64 - 0.5;
+                let v = lo as f64 + (hi - lo) as f64 * k / c as f64;
+                return Some((v as u64).clamp(self.min, self.max));
+            }
+            below += c;
+        }
+        Some(self.max)
+    }
+
+    /// Non-empty buckets as (lo, hi, count)
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.counts.iter().enumerate().filter(|(_, c)| **c > 0).map(|(i, c)| {
+            let (lo, hi) = Self::bucket_range(i);
+            (lo, hi, *c)
+        })
+    }
+}
+
+// Outcome counts and stopping-time distribution over the whole run
+pub struct RunStats {
+    pub reaches_one: u64,
+    pub nontrivial_cycle: u64,
+    pub steps_overflow: u64,
+    pub stopping: StoppingTimes,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        RunStats { reaches_one: 0, nontrivial_cycle: 0, steps_overflow: 0, stopping: StoppingTimes::new() }
+    }
+
+    pub fn record(&mut self, outcome: Outcome, stopping_time: Option<u64>) {
+        match outcome {
+            Outcome::ReachesOne => self.reaches_one += 1,
+            Outcome::NontrivialCycle => self.nontrivial_cycle += 1,
+            Outcome::StepsOverflow => self.steps_overflow += 1,
+        }
+        if let Some(t) = stopping_time { self.stopping.record(t); }
+    }
+}
+
+// Writes periodic progress records and the final summary to stderr or a file
+pub struct Progress {
+    format: ProgressFormat,
+    out: Box<dyn Write + Send>,
+    interval: Duration,
+    count: Option<u64>,
+    started: Instant,
+    last_at: Instant,
+    last_processed: u64,
+}
+
+impl Progress {
+    pub fn new(format: ProgressFormat, path: Option<&str>, interval: Duration, count: Option<u64>) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match path {
+            Some(p) => Box::new(BufWriter::new(File::create(p)?)),
+            None => Box::new(io::stderr()),
+        };
+        let now = Instant::now();
+        Ok(Progress { format, out, interval, count, started: now, last_at: now, last_processed: 0 })
+    }
+
+    /// Called once per start, before `processed` is incremented for it
+    pub fn tick(&mut self, processed: u64, frontier: &BigUint) -> io::Result<()> {
+        match self.format {
+            ProgressFormat::Text => {
+                if processed.is_multiple_of(10000) {
+                    writeln!(self.out, "Processed {processed} starts (up to {frontier})")?;
+                    self.out.flush()?;
+                }
+            }
+            ProgressFormat::Json => {
+                let now = Instant::now();
+                let window = now.duration_since(self.last_at);
+                if window < self.interval { return Ok(()); }
+                let elapsed = now.duration_since(self.started).as_secs_f64();
+                let rate = processed.saturating_sub(self.last_processed) as f64 / window.as_secs_f64().max(1e-9);
+                let avg_rate = processed as f64 / elapsed.max(1e-9);
+                let eta = match self.count {
+                    Some(limit) if avg_rate > 0.0 => format!("{:.1}", limit.saturating_sub(processed) as f64 / avg_rate),
+                    _ => "null".to_string(),
+                };
+                writeln!(
+                    self.out,
+                    "{{\"type\":\"progress\",\"elapsed_s\":{elapsed:.3},\"processed\":{processed},\"rate\":{rate:.2},\"avg_rate\":{avg_rate:.2},\"frontier\":\"{frontier}\",\"frontier_bits\":{},\"eta_s\":{eta}}}",
+                    frontier.bits()
+                )?;
+                self.out.flush()?;
+                self.last_at = now;
+                self.last_processed = processed;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self, stats: &RunStats, processed: u64, stop_reason: &str, last: Option<&BigUint>) -> io::Result<()> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = processed as f64 / elapsed.max(1e-9);
+        let st = &stats.stopping;
+        match self.format {
+            ProgressFormat::Text => {
+                let out = &mut self.out;
+                writeln!(out, "---- summary ----")?;
+                writeln!(out, "stopped: {stop_reason}")?;
+                writeln!(out, "processed: {processed} starts in {elapsed:.1}s ({rate:.1} starts/s)")?;
+                if let Some(last) = last {
+                    writeln!(out, "last start: {last}")?;
+                }
+                writeln!(out, "outcomes: reaches_one={} nontrivial_cycle={} steps_overflow={}", stats.reaches_one, stats.nontrivial_cycle, stats.steps_overflow)?;
+                if st.n > 0 {
+                    writeln!(out, "stopping times: min={} mean={:.1} max={}", st.min, st.mean(), st.max)?;
+                    let pct = |p| st.percentile(p).unwrap_or(0);
+                    writeln!(out, "  ~p50={} ~p90={} ~p99={}", pct(50.0), pct(90.0), pct(99.0))?;
+                    for (lo, hi, c) in st.buckets() {
+                        writeln!(out, "  [{lo}, {hi}): {c}")?;
+                    }
+                }
+            }
+            ProgressFormat::Json => {
+                let last = last.map(|n| format!("\"{n}\"")).unwrap_or_else(|| "null".to_string());
+                let hist: Vec<String> = st.buckets().map(|(lo, hi, c)| format!("{{\"lo\":{lo},\"hi\":{hi},\"count\":{c}}}")).collect();
+                let (min, max) = if st.n > 0 { (st.min.to_string(), st.max.to_string()) } else { ("null".into(), "null".into()) };
+                let pct = |p| st.percentile(p).map_or_else(|| "null".to_string(), |v| v.to_string());
+                writeln!(
+                    self.out,
+                    "{{\"type\":\"summary\",\"stopped\":\"{stop_reason}\",\"elapsed_s\":{elapsed:.3},\"processed\":{processed},\"rate\":{rate:.2},\"last_start\":{last},\"outcomes\":{{\"reaches_one\":{},\"nontrivial_cycle\":{},\"steps_overflow\":{}}},\"stopping_time\":{{\"count\":{},\"min\":{min},\"max\":{max},\"mean\":{:.3},\"p50\":{},\"p90\":{},\"p99\":{},\"histogram\":[{}]}}}}",
+                    stats.reaches_one, stats.nontrivial_cycle, stats.steps_overflow, st.n, st.mean(), pct(50.0), pct(90.0), pct(99.0), hist.join(",")
+                )?;
+            }
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_tile_the_values_they_hold() {
+        // Each bucket starts where the previous one ends, and holds what falls in it
+        let mut end = 0;
+        for idx in 0..STOP_BUCKETS {
+            let (lo, hi) = StoppingTimes::bucket_range(idx);
+            assert_eq!(lo, end, "bucket {idx}");
+            assert!(lo < hi);
+            assert_eq!(StoppingTimes::bucket_of(lo), idx);
+            assert_eq!(StoppingTimes::bucket_of(hi - 1), idx);
+            end = hi;
+        }
+        // The top bucket runs to the end of u64 instead of wrapping to 0
+        assert_eq!(StoppingTimes::bucket_range(STOP_BUCKETS - 1), (7 << 61, u64::MAX));
+        assert_eq!(StoppingTimes::bucket_of(u64::MAX), STOP_BUCKETS - 1);
+        // Four sub-buckets per power of two
+        assert_eq!(StoppingTimes::bucket_range(4), (4, 5));
+        assert_eq!(StoppingTimes::bucket_range(8), (8, 10));
+        assert_eq!(StoppingTimes::bucket_range(StoppingTimes::bucket_of(1000)), (896, 1024));
+    }
+
+    #[test]
+    fn histogram_counts_and_moments() {
+        let mut st = StoppingTimes::new();
+        for v in [0, 3, 3, 7, 111, 111, 118] { st.record(v); }
+        assert_eq!((st.n, st.min, st.max), (7, 0, 118));
+        assert!((st.mean() - 353.0 / 7.0).abs() < 1e-9);
+        let buckets: Vec<(u64, u64, u64)> = st.buckets().collect();
+        assert_eq!(buckets, [(0, 1, 1), (3, 4, 2), (7, 8, 1), (96, 112, 2), (112, 128, 1)]);
+    }
+
+    #[test]
+    fn percentiles_land_in_the_right_bucket() {
+        let mut st = StoppingTimes::new();
+        assert_eq!(st.percentile(50.0), None);
+        for v in [0, 3, 3, 7, 111, 111, 118] { st.record(v); }
+        // Width-one buckets are exact; wider ones are clamped to the observed range
+        assert_eq!(st.percentile(0.0), Some(0));
+        assert_eq!(st.percentile(50.0), Some(7));
+        assert_eq!(st.percentile(70.0), Some(100));
+        assert_eq!(st.percentile(100.0), Some(118));
+
+        let mut st = StoppingTimes::new();
+        for v in 1..=10_000 { st.record(v); }
+        for p in [10.0, 50.0, 90.0, 99.0] {
+            let exact = p * 100.0;
+            let est = st.percentile(p).unwrap() as f64;
+            assert!((est - exact).abs() <= exact / 8.0, "p{p}: {est} vs {exact}");
+        }
+
+        let mut st = StoppingTimes::new();
+        st.record(u64::MAX);
+        st.record(u64::MAX - 1);
+        for p in [0.0, 50.0, 100.0] { assert!(st.percentile(p).unwrap() >= u64::MAX - 1); }
+    }
+
+    fn written(format: ProgressFormat, count: Option<u64>, run: impl FnOnce(&mut Progress)) -> String {
+        let path = std::env::temp_dir().join(format!("collatz-progress-{}-{:?}.log", std::process::id(), format));
+        let mut p = Progress::new(format, path.to_str(), Duration::ZERO, count).unwrap();
+        run(&mut p);
+        drop(p);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        text
+    }
+
+    #[test]
+    fn json_records_are_one_object_per_line() {
+        let mut stats = RunStats::new();
+        stats.record(Outcome::ReachesOne, Some(111));
+        let text = written(ProgressFormat::Json, Some(10), |p| {
+            p.tick(4, &BigUint::from(27u32)).unwrap();
+            p.finish(&stats, 5, "count reached", Some(&BigUint::from(27u32))).unwrap();
+        });
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert!(line.starts_with('{') && line.ends_with('}'), "{line}");
+            assert_eq!(line.matches('{').count(), line.matches('}').count(), "{line}");
+        }
+        assert!(lines[0].starts_with("{\"type\":\"progress\",\"elapsed_s\":"), "{}", lines[0]);
+        assert!(lines[0].contains(",\"processed\":4,\"rate\":"), "{}", lines[0]);
+        assert!(lines[0].contains(",\"frontier\":\"27\",\"frontier_bits\":5,\"eta_s\":"), "{}", lines[0]);
+        let keys: Vec<&str> = lines[0].split('"').skip(1).step_by(2).filter(|k| lines[0].contains(&format!("\"{k}\":"))).collect();
+        assert_eq!(keys, ["type", "elapsed_s", "processed", "rate", "avg_rate", "frontier", "frontier_bits", "eta_s"]);
+        assert!(lines[1].starts_with("{\"type\":\"summary\",\"stopped\":\"count reached\",\"elapsed_s\":"), "{}", lines[1]);
+        assert!(lines[1].contains(",\"processed\":5,\"rate\":"), "{}", lines[1]);
+        assert!(lines[1].ends_with(
+            ",\"last_start\":\"27\",\"outcomes\":{\"reaches_one\":1,\"nontrivial_cycle\":0,\"steps_overflow\":0},\
+             \"stopping_time\":{\"count\":1,\"min\":111,\"max\":111,\"mean\":111.000,\"p50\":111,\"p90\":111,\"p99\":111,\"histogram\":[{\"lo\":96,\"hi\":112,\"count\":1}]}}"
+        ), "{}", lines[1]);
+    }
+
+    #[test]
+    fn text_progress_every_ten_thousand_starts() {
+        let text = written(ProgressFormat::Text, None, |p| {
+            for n in [0, 1, 9_999, 10_000, 20_000] { p.tick(n, &BigUint::from(n + 1)).unwrap(); }
+        });
+        assert_eq!(text, "Processed 0 starts (up to 1)\nProcessed 10000 starts (up to 10001)\nProcessed 20000 starts (up to 20001)\n");
+    }
+}
@@ -0,0 +1,317 @@
+// ---------- Property tests: fast paths against the BigUint reference ----------
+//
+// A small hand-rolled harness. `check` draws inputs from a seeded Rng, runs a
+// property on each, and on failure shrinks the input to a smaller one that still
+// fails before panicking with the seed needed to replay it:
+//
+//   COLLATZ_PROP_SEED=<seed> COLLATZ_PROP_CASES=<n> cargo test props::
+//
+// Every accelerated stepping path and every cycle detector gets a property
+// here comparing it with `collatz_next` walked one step at a time.
+
+use std::fmt::Debug;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{CheckedSub, One, ToPrimitive, Zero};
+
+use crate::cache::OrbitCache;
+use crate::codec::EncodedOrbit;
+use crate::backend::{Backend, Big};
+use crate::lanes::LaneEngine;
+use crate::limbs::LimbBuf;
+use crate::maps::{StepMap, detect_outcome_in, detect_outcome_map, shortcut_next, syracuse_next};
+use crate::rng::{RandomSource, Rng};
+use crate::{Outcome, collatz_next, detect_outcome, numexpr};
+
+const DEFAULT_SEED: u64 = 0x5EED_C011_A72F_0042;
+const DEFAULT_CASES: usize = 200;
+
+fn env_u64(key: &str) -> Option<u64> {
+    std::env::var(key).ok().and_then(|v| v.parse().ok())
+}
+
+/// Run `prop` on `cases` generated inputs; shrink and report the first failure
+pub fn check<T: Debug + Clone>(
+    name: &str,
+    mut generate: impl FnMut(&mut Rng) -> T,
+    shrink: impl Fn(&T) -> Vec<T>,
+    mut prop: impl FnMut(&T) -> Result<(), String>,
+) {
+    let seed = env_u64("COLLATZ_PROP_SEED").unwrap_or(DEFAULT_SEED);
+    let cases = env_u64("COLLATZ_PROP_CASES").map_or(DEFAULT_CASES, |n| n as usize);
+    let mut rng = Rng::from_seed(seed);
+    for case in 0..cases {
+        let input = generate(&mut rng);
+        let Err(first) = prop(&input) else { continue };
+        // Greedy shrink: keep taking the first smaller candidate that still fails
+        let (mut input, mut err) = (input, first);
+        let mut rounds = 0;
+        'shrink: while rounds < 10_000 {
+            rounds += 1;
+            for candidate in shrink(&input) {
+                if let Err(e) = prop(&candidate) {
+                    (input, err) = (candidate, e);
+                    continue 'shrink;
+                }
+            }
+            break;
+        }
+        panic!("property '{name}' failed on case {case} (COLLATZ_PROP_SEED={seed}): {err}\n  input: {input:?}");
+    }
+}
+
+/// Smaller variants of a start, most aggressive first
+pub fn shrink_biguint(n: &BigUint) -> Vec<BigUint> {
+    let mut out = Vec::new();
+    if n.bits() > 1 {
+        out.push(n >> (n.bits() / 2));
+        out.push(n >> 1u32);
+        let mut top_cleared = n.clone();
+        top_cleared.set_bit(n.bits() - 1, false);
+        out.push(top_cleared);
+    }
+    if *n > BigUint::one() { out.push(n - 1u32); }
+    out.retain(|c| !c.is_zero() && c < n);
+    out
+}
+
+/// Random start with a bias toward the interesting places: word boundaries
+/// (2^64, 2^128 and friends), all-ones values (long climbs) and small numbers
+pub fn gen_start(rng: &mut Rng, max_bits: u64) -> BigUint {
+    let pow = |k: u64| BigUint::one() << k;
+    let delta = |rng: &mut Rng| BigUint::from(rng.next_u64() % 1024);
+    let boundaries: Vec<u64> = [32u64, 63, 64, 65, 127, 128, 129, 192, 256].into_iter().filter(|&k| k < max_bits).collect();
+    let n = match rng.next_u64() % 8 {
+        // Just above or below a limb boundary, where native fast paths overflow
+        0 | 1 if !boundaries.is_empty() => {
+            let k = boundaries[(rng.next_u64() % boundaries.len() as u64) as usize];
+            let d = delta(rng);
+            if rng.next_u64() & 1 == 0 { pow(k) + d } else { pow(k).checked_sub(&d).unwrap_or_default() }
+        }
+        // 2^k - 1: climbs for k steps before it starts to fall
+        2 => pow(1 + rng.next_u64() % max_bits) - 1u32,
+        3 => BigUint::from(1 + rng.next_u64() % 100_000),
+        // Uniform bit length, then uniform value of that length
+        _ => {
+            let bits = 1 + rng.next_u64() % max_bits;
+            rng.gen_range_biguint(&pow(bits - 1), &(pow(bits) - 1u32))
+        }
+    };
+    n.max(BigUint::one())
+}
+
+/// Reference: step with `collatz_next` until 1, tracking steps and peak
+pub fn reference_walk(start: &BigUint) -> (u64, BigUint) {
+    let (mut x, mut steps, mut peak) = (start.clone(), 0u64, start.clone());
+    while !x.is_one() {
+        x = collatz_next(&x);
+        steps += 1;
+        if x > peak { peak = x.clone(); }
+    }
+    (steps, peak)
+}
+
+#[test]
+fn floyd_detector_matches_reference_walk() {
+    check("detect_outcome", |r| gen_start(r, 300), shrink_biguint, |n| {
+        let c = detect_outcome(n);
+        let (steps, peak) = reference_walk(n);
+        if c.outcome != Outcome::ReachesOne { return Err(format!("outcome {:?}", c.outcome)); }
+        if c.stopping_time != Some(steps) { return Err(format!("stopping time {:?}, reference {steps}", c.stopping_time)); }
+        if c.peak != peak { return Err(format!("peak {}, reference {peak}", c.peak)); }
+        Ok(())
+    });
+}
+
+#[test]
+fn every_map_classifies_like_the_reference_walk() {
+    check("detect_outcome_map", |r| gen_start(r, 300), shrink_biguint, |n| {
+        let (steps, peak) = reference_walk(n);
+        for map in StepMap::ALL {
+            let c = detect_outcome_map(n, map);
+            if c.stopping_time != Some(steps) || c.peak != peak {
+                return Err(format!("{}: {:?}, reference {steps} steps, peak {peak}", map.name(), c));
+            }
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn map_steps_convert_to_standard_steps() {
+    check("StepMap::step", |r| gen_start(r, 300), shrink_biguint, |n| {
+        // The shortcut map skips exactly the halving after each 3x+1
+        let std = collatz_next(n);
+        let want = if n.is_odd() { collatz_next(&std) } else { std };
+        if shortcut_next(n) != want { return Err(format!("shortcut gave {}, want {want}", shortcut_next(n))); }
+        // Syracuse: walk standard steps until the next odd value
+        let (got, count) = syracuse_next(n);
+        let (mut x, mut steps) = (n.clone(), 0u64);
+        loop {
+            x = collatz_next(&x);
+            steps += 1;
+            if x.is_odd() { break; }
+        }
+        if (got.clone(), count) != (x.clone(), steps) { return Err(format!("syracuse gave ({got}, {count}), want ({x}, {steps})")); }
+        Ok(())
+    });
+}
+
+#[test]
+fn in_place_steps_match_biguint_steps() {
+    check("LimbBuf::step", |r| gen_start(r, 300), shrink_biguint, |n| {
+        for map in StepMap::ALL {
+            // A few hundred steps: long enough to cross limb boundaries both ways
+            let (mut buf, mut x) = (LimbBuf::from_biguint(n), n.clone());
+            for i in 0..300 {
+                if x.is_one() { break; }
+                let (next, want) = map.step(&x);
+                let got = buf.step(map);
+                x = next;
+                if got != want || buf.to_biguint() != x || buf.bits() != x.bits() {
+                    return Err(format!("{} step {i}: {} ({got} steps), want {x} ({want} steps)", map.name(), buf.to_biguint()));
+                }
+            }
+        }
+        Ok(())
+    });
+}
+
+/// What every arithmetic backend has to get right, against plain BigUint arithmetic
+fn backend_suite<N: Backend>(name: &str) {
+    check(&format!("backend {name}"), |r| (gen_start(r, 300), gen_start(r, 300)), |(a, b)| {
+        shrink_biguint(a).into_iter().map(|a| (a, b.clone())).collect()
+    }, |(a, b)| {
+        let (x, y) = (N::from_biguint(a), N::from_biguint(b));
+        if x.to_biguint() != *a { return Err("BigUint round trip".into()); }
+        if x.to_string() != a.to_string() { return Err(format!("formats as {x}")); }
+        if x.cmp(&y) != a.cmp(b) || (x == y) != (a == b) { return Err(format!("compares wrongly with {b}")); }
+        if x.bits() != a.bits() || x.is_odd() != a.is_odd() || x.is_one() != a.is_one() {
+            return Err(format!("bits/parity: {} {}", x.bits(), x.is_odd()));
+        }
+        if x.trailing_zeros() != a.trailing_zeros().unwrap_or(0) { return Err(format!("trailing zeros {}", x.trailing_zeros())); }
+        let mut t = x.clone();
+        t.triple_plus_one();
+        if t.to_biguint() != a * 3u32 + 1u32 { return Err(format!("3n+1 gave {t}")); }
+        let k = b.bits() % 200;
+        t.shr_assign(k);
+        if t.to_biguint() != (a * 3u32 + 1u32) >> k { return Err(format!("shift by {k} gave {t}")); }
+        t.copy_from(&y);
+        if t != y { return Err("copy_from".into()); }
+        for map in StepMap::ALL {
+            let mut t = x.clone();
+            let (want, steps) = map.step(a);
+            if t.step(map) != steps || t.to_biguint() != want { return Err(format!("{} step gave {t}", map.name())); }
+            if detect_outcome_in::<N>(a, map) != detect_outcome_map(a, StepMap::Standard) {
+                return Err(format!("{} detector disagrees", map.name()));
+            }
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn num_bigint_backend_passes_the_suite() { backend_suite::<Big>("num-bigint") }
+
+#[test]
+fn limb_backend_passes_the_suite() { backend_suite::<LimbBuf>("limbs") }
+
+#[cfg(feature = "gmp")]
+#[test]
+fn gmp_backend_passes_the_suite() { backend_suite::<crate::gmp::Gmp>("gmp") }
+
+#[test]
+fn orbit_cache_matches_floyd_detector() {
+    // One cache across all cases so later starts hit entries left by earlier ones,
+    // including the map above the dense range and peaks that overflow u128
+    let mut cache = OrbitCache::new(8 << 20, 1 << 16);
+    check("OrbitCache::classify", |r| {
+        let base = gen_start(r, 160);
+        // Short runs of consecutive starts, as in a sequential scan
+        (0..4u32).map(|i| &base + i).collect::<Vec<_>>()
+    }, |v| if v.len() > 1 { vec![v[..v.len() / 2].to_vec()] } else { Vec::new() }, |starts| {
+        for n in starts {
+            let (got, want) = (cache.classify(n), detect_outcome(n));
+            if got != want { return Err(format!("start {n}: cache {got:?}, Floyd {want:?}")); }
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn lane_engine_matches_scalar_path() {
+    check("LaneEngine", |r| {
+        // Ranges from 1, in the middle of u64 and right at its top, with memos
+        // from none at all to plenty
+        let len = 1 + r.next_u64() % 600;
+        let base = match r.next_u64() % 3 {
+            0 => 1 + r.next_u64() % 1000,
+            1 => gen_start(r, 64).to_u64().unwrap_or(1).max(1),
+            _ => u64::MAX - len - r.next_u64() % 1000,
+        };
+        let memo = [0u64, 1 << 10, 1 << 20][(r.next_u64() % 3) as usize];
+        (base, len, memo)
+    }, |&(base, len, memo)| {
+        let mut v = vec![(base, len / 2, memo)];
+        if base > 1 { v.push((base / 2, len, memo)); }
+        v.retain(|&(_, l, _)| l > 0);
+        v
+    }, |&(base, len, memo)| {
+        let mut engine = LaneEngine::new(base, base + len, memo);
+        for want_n in base..base + len {
+            let Some((n, c)) = engine.next_result() else { return Err(format!("ran out before {want_n}")); };
+            if n != want_n { return Err(format!("got {n} where {want_n} was due")); }
+            let want = detect_outcome(&BigUint::from(n));
+            if c != want { return Err(format!("start {n}: engine {c:?}, scalar {want:?}")); }
+        }
+        if engine.next_result().is_some() { return Err("results past the end".into()); }
+        Ok(())
+    });
+}
+
+#[test]
+fn parity_codec_replays_the_reference_orbit() {
+    check("EncodedOrbit", |r| gen_start(r, 200), shrink_biguint, |n| {
+        let orbit = EncodedOrbit::encode(n, u64::MAX);
+        let mut buf = Vec::new();
+        orbit.write_to(&mut buf).map_err(|e| e.to_string())?;
+        let decoded = EncodedOrbit::read_from(&mut buf.as_slice(), buf.len() as u64).map_err(|e| e.to_string())?;
+        if decoded != orbit { return Err("record does not round-trip".into()); }
+        let mut x = n.clone();
+        for (i, v) in decoded.replay().enumerate() {
+            let v = v?;
+            if v != x { return Err(format!("step {i}: replayed {v}, reference {x}")); }
+            x = collatz_next(&x);
+        }
+        if decoded.steps != reference_walk(n).0 { return Err(format!("{} steps encoded", decoded.steps)); }
+        Ok(())
+    });
+}
+
+#[test]
+fn numeric_expressions_round_trip() {
+    check("numexpr", |r| gen_start(r, 600), shrink_biguint, |n| {
+        let forms = [n.to_string(), format!("0x{}", n.to_str_radix(16)), format!("0b{}", n.to_str_radix(2))];
+        for f in &forms {
+            if numexpr::parse_biguint(f).as_ref() != Ok(n) { return Err(format!("'{f}' did not parse back")); }
+        }
+        let k = n.bits();
+        let expr = format!("2^{k} - {}", (BigUint::one() << k) - n);
+        if numexpr::parse_biguint(&expr).as_ref() != Ok(n) { return Err(format!("'{expr}' did not evaluate back")); }
+        if let Some(v) = n.to_u64() && numexpr::parse_u64(&n.to_string()) != Ok(v) { return Err("parse_u64 disagrees".into()); }
+        Ok(())
+    });
+}
+
+#[test]
+fn harness_shrinks_to_a_minimal_failure() {
+    // A deliberately false property: shrinking should walk down to its smallest counterexample
+    let result = std::panic::catch_unwind(|| {
+        check("demo", |r| gen_start(r, 100), shrink_biguint, |n| {
+            if *n >= BigUint::from(1000u32) { Err("too big".into()) } else { Ok(()) }
+        })
+    });
+    let msg = *result.expect_err("property should fail").downcast::<String>().unwrap();
+    assert!(msg.contains("input: 1000"), "{msg}");
+}
@@ -0,0 +1,301 @@
+// ---------- Random number generators (`--rng`) ----------
+//
+// Random mode only needs a stream of u64s; `RandomSource` turns one into
+// uniform BigUints. Three generators, all fully determined by a u64 seed:
+//   xorshift   xorshift128+, the fastest, but weak in its lowest bits (default)
+//   counter    SplitMix64 output mixing of key + i·γ; word i is a function of
+//              i alone, so streams split and skip ahead for free, and each seed
+//              (e.g. one per dist unit) is an independent stream
+//   chacha     ChaCha20 keyed by the seed, for when statistical quality matters
+//              more than speed
+// `Rng` picks one at run time and is what the rest of the crate passes around.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use num_bigint::BigUint;
+
+// Weyl increment of SplitMix64 (2^64 / golden ratio, odd)
+const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// SplitMix64's output function, a bijection on u64
+fn mix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+pub trait RandomSource {
+    fn next_u64(&mut self) -> u64;
+
+    /// Uniform value in [low, high_inclusive]. Offsets are drawn bit-exact: just
+    /// enough words for the largest offset, the top one masked to its bit length,
+    /// and redrawn when above it. That offset has its top bit set, so each draw is
+    /// accepted with probability over 1/2 and fewer than 2 draws are expected.
+    fn gen_range_biguint(&mut self, low: &BigUint, high_inclusive: &BigUint) -> BigUint {
+        if low >= high_inclusive { return low.clone(); }
+        let max = high_inclusive - low;
+        let bits = max.bits();
+        let words = bits.div_ceil(64) as usize;
+        let top_mask = u64::MAX >> (words as u64 * 64 - bits);
+        let mut digits = vec![0u32; 2 * words];
+        loop {
+            for (i, pair) in digits.chunks_exact_mut(2).enumerate() {
+                let r = if i + 1 == words { self.next_u64() & top_mask } else { self.next_u64() };
+                pair[0] = r as u32;
+                pair[1] = (r >> 32) as u32;
+            }
+            let v = BigUint::from_slice(&digits);
+            if v <= max { return low + v; }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Xorshift128Plus { s0: u64, s1: u64 }
+
+impl Xorshift128Plus {
+    pub fn from_seed(seed: u64) -> Self {
+        // splitmix64 to spread the seed over both state words
+        let s0 = mix64(seed.wrapping_add(GAMMA));
+        let mut s1 = mix64(seed.wrapping_add(GAMMA.wrapping_mul(2)));
+        if s0 == 0 && s1 == 0 { s1 = 1; }
+        Xorshift128Plus { s0, s1 }
+    }
+}
+
+impl RandomSource for Xorshift128Plus {
+    fn next_u64(&mut self) -> u64 {
+        let mut s1 = self.s0;
+        let s0 = self.s1;
+        self.s0 = s0;
+        s1 ^= s1 << 23;
+        self.s1 = s1 ^ s0 ^ (s1 >> 18) ^ (s0 >> 5);
+        self.s1.wrapping_add(s0)
+    }
+}
+
+/// Counter-based SplitMix64: word i of the stream is mix64(key + (i+1)·γ)
+#[derive(Debug, Clone)]
+pub struct CounterRng { key: u64, counter: u64 }
+
+impl CounterRng {
+    pub fn from_seed(seed: u64) -> Self { CounterRng { key: mix64(seed), counter: 0 } }
+
+    /// Make word `index` (0-based) the next one drawn
+    pub fn seek(&mut self, index: u64) { self.counter = index; }
+}
+
+impl RandomSource for CounterRng {
+    fn next_u64(&mut self) -> u64 {
+        self.counter = self.counter.wrapping_add(1);
+        mix64(self.key.wrapping_add(self.counter.wrapping_mul(GAMMA)))
+    }
+}
+
+/// The ChaCha20 block function (RFC 8439) on a key and the last four state
+/// words (block counter and nonce)
+fn chacha20_block(key: &[u32; 8], tail: [u32; 4]) -> [u32; 16] {
+    let mut init = [0u32; 16];
+    init[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]); // "expand 32-byte k"
+    init[4..12].copy_from_slice(key);
+    init[12..].copy_from_slice(&tail);
+    let mut s = init;
+    let quarter = |s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+        s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(16);
+        s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(12);
+        s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(8);
+        s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(7);
+    };
+    for _ in 0..10 {
+        quarter(&mut s, 0, 4, 8, 12);
+        quarter(&mut s, 1, 5, 9, 13);
+        quarter(&mut s, 2, 6, 10, 14);
+        quarter(&mut s, 3, 7, 11, 15);
+        quarter(&mut s, 0, 5, 10, 15);
+        quarter(&mut s, 1, 6, 11, 12);
+        quarter(&mut s, 2, 7, 8, 13);
+        quarter(&mut s, 3, 4, 9, 14);
+    }
+    for (w, i) in s.iter_mut().zip(init) { *w = w.wrapping_add(i); }
+    s
+}
+
+/// ChaCha20 keystream as u64s: 256-bit key expanded from the seed, 64-bit
+/// block counter, zero nonce
+#[derive(Debug, Clone)]
+pub struct ChaChaRng { key: [u32; 8], block: u64, buf: [u32; 16], used: usize }
+
+impl ChaChaRng {
+    pub fn from_seed(seed: u64) -> Self {
+        let mut key = [0u32; 8];
+        for (i, pair) in key.chunks_exact_mut(2).enumerate() {
+            let w = mix64(seed.wrapping_add(GAMMA.wrapping_mul(i as u64 + 1)));
+            pair[0] = w as u32;
+            pair[1] = (w >> 32) as u32;
+        }
+        ChaChaRng { key, block: 0, buf: [0; 16], used: 16 }
+    }
+
+    /// Make word `index` (0-based) the next one drawn; a block holds 8 words
+    pub fn seek(&mut self, index: u64) {
+        (self.block, self.used) = (index / 8, 16);
+        for _ in 0..index % 8 { self.next_u64(); }
+    }
+}
+
+impl RandomSource for ChaChaRng {
+    fn next_u64(&mut self) -> u64 {
+        if self.used == 16 {
+            self.buf = chacha20_block(&self.key, [self.block as u32, (self.block >> 32) as u32, 0, 0]);
+            self.block = self.block.wrapping_add(1);
+            self.used = 0;
+        }
+        let w = self.buf[self.used] as u64 | (self.buf[self.used + 1] as u64) << 32;
+        self.used += 2;
+        w
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RngKind {
+    #[default]
+    Xorshift,
+    Counter,
+    ChaCha,
+}
+
+impl RngKind {
+    pub const ALL: [RngKind; 3] = [RngKind::Xorshift, RngKind::Counter, RngKind::ChaCha];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "xorshift" | "xorshift128+" => Some(RngKind::Xorshift),
+            "counter" | "splitmix" => Some(RngKind::Counter),
+            "chacha" | "chacha20" => Some(RngKind::ChaCha),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RngKind::Xorshift => "xorshift",
+            RngKind::Counter => "counter",
+            RngKind::ChaCha => "chacha",
+        }
+    }
+}
+
+/// One of the generators, chosen at run time
+#[derive(Debug, Clone)]
+pub enum Rng {
+    Xorshift(Xorshift128Plus),
+    Counter(CounterRng),
+    ChaCha(ChaChaRng),
+}
+
+impl Rng {
+    /// Deterministic generator of the given kind (used to hand out reproducible random work units)
+    pub fn new(kind: RngKind, seed: u64) -> Self {
+        match kind {
+            RngKind::Xorshift => Rng::Xorshift(Xorshift128Plus::from_seed(seed)),
+            RngKind::Counter => Rng::Counter(CounterRng::from_seed(seed)),
+            RngKind::ChaCha => Rng::ChaCha(ChaChaRng::from_seed(seed)),
+        }
+    }
+
+    /// The default generator for a given seed
+    pub fn from_seed(seed: u64) -> Self { Rng::new(RngKind::default(), seed) }
+
+    /// The default generator seeded from the clock
+    pub fn seeded() -> Self { Rng::from_seed(clock_seed()) }
+
+    /// Make word `index` (0-based) of the stream the next one drawn, for the
+    /// generators that can get there without drawing the words before it
+    pub fn seek(&mut self, index: u64) -> Result<(), String> {
+        match self {
+            Rng::Counter(r) => r.seek(index),
+            Rng::ChaCha(r) => r.seek(index),
+            Rng::Xorshift(_) => return Err("the xorshift generator cannot seek; use counter or chacha".into()),
+        }
+        Ok(())
+    }
+}
+
+impl RandomSource for Rng {
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Rng::Xorshift(r) => r.next_u64(),
+            Rng::Counter(r) => r.next_u64(),
+            Rng::ChaCha(r) => r.next_u64(),
+        }
+    }
+}
+
+/// A seed from the current time, for runs that do not ask for one
+pub fn clock_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    mix64(nanos as u64 ^ mix64((nanos >> 64) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chacha20_block_matches_rfc_8439() {
+        // Test vector of RFC 8439, section 2.3.2
+        let key: [u32; 8] = std::array::from_fn(|i| u32::from_le_bytes(std::array::from_fn(|j| (4 * i + j) as u8)));
+        let out = chacha20_block(&key, [1, 0x0900_0000, 0x4a00_0000, 0]);
+        assert_eq!(out, [
+            0xe4e7f110, 0x15593bd1, 0x1fdd0f50, 0xc47120a3, 0xc7f4d1c7, 0x0368c033, 0x9aaa2204, 0x4e6cd4c3,
+            0x466482d2, 0x09aa9f07, 0x05d7c214, 0xa2028bd9, 0xd19c12b5, 0xb94e16de, 0xe883d0cb, 0x4e3c50a2,
+        ]);
+    }
+
+    #[test]
+    fn every_generator_is_deterministic_per_seed() {
+        for kind in RngKind::ALL {
+            let (mut a, mut b) = (Rng::new(kind, 42), Rng::new(kind, 42));
+            let xs: Vec<u64> = (0..100).map(|_| a.next_u64()).collect();
+            assert!(xs.iter().all(|&x| x == b.next_u64()), "{}", kind.name());
+            assert_ne!(Rng::new(kind, 1).next_u64(), Rng::new(kind, 2).next_u64(), "{}", kind.name());
+            assert_eq!(RngKind::parse(kind.name()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn seekable_streams_are_random_access() {
+        // Any word can be had without generating the ones before it, in any order
+        for kind in [RngKind::Counter, RngKind::ChaCha] {
+            let mut a = Rng::new(kind, 7);
+            let xs: Vec<u64> = (0..50).map(|_| a.next_u64()).collect();
+            let mut b = Rng::new(kind, 7);
+            for i in [30, 0, 49, 8, 7, 15, 16] {
+                b.seek(i as u64).unwrap();
+                assert_eq!(b.next_u64(), xs[i], "{} word {i}", kind.name());
+                if i < 49 { assert_eq!(b.next_u64(), xs[i + 1], "{} word {}", kind.name(), i + 1); }
+            }
+        }
+        let mut c = CounterRng::from_seed(7);
+        c.seek(49);
+        assert_eq!(c.next_u64(), mix64(mix64(7).wrapping_add(GAMMA.wrapping_mul(50))));
+        assert!(Rng::new(RngKind::Xorshift, 7).seek(1).is_err());
+    }
+
+    #[test]
+    fn low_bits_are_balanced() {
+        // Each of the lowest 8 bits set about half the time; over 40000 words the
+        // allowed ±800 is eight standard deviations
+        for kind in RngKind::ALL {
+            let mut r = Rng::new(kind, 49);
+            let mut ones = [0u32; 8];
+            for _ in 0..40_000 {
+                let x = r.next_u64();
+                for (b, n) in ones.iter_mut().enumerate() { *n += (x >> b) as u32 & 1; }
+            }
+            for (b, &n) in ones.iter().enumerate() {
+                assert!((19_200..=20_800).contains(&n), "{}: bit {b} set {n} times", kind.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// ---------- Sampling strategies for random mode (`--sampling`) ----------
+//
+// Uniform sampling of [2^68, 2^2000) tests almost nothing but 2000-bit values
+// and leaves the residue classes to chance. Two alternatives:
+//   stratified  round-robin over the residue classes mod 2^k that survive the
+//               sieve (see lanes.rs), each with a uniform high part, so after
+//               r rounds every such class has exactly r samples. The other
+//               classes drop below their start within k steps, so their orbits
+//               continue as smaller starts
+//   quasi       the R2 low-discrepancy sequence (Roberts) over the unit square,
+//               mapped to (bit length, top 64 bits below the leading one); the
+//               bits underneath are uniform. Bit lengths are covered evenly
+//               instead of in proportion to how many values have them
+// Both start at a random offset drawn from the run's Rng, so seeds still matter.
+// `Coverage` tallies what a run actually hit, whatever the strategy.
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::lanes::sieve;
+use crate::rng::{RandomSource, Rng};
+
+// Increments of the R2 sequence: 1/g and 1/g^2 for the plastic number g, as
+// fractions of 2^64
+const R2_STEP: (u64, u64) = (0xc13f_a9a9_02a6_328f, 0x91e1_0da5_c79e_7b1c);
+// Bit-length bands in the coverage report
+const BANDS: u64 = 32;
+// Quasi points in a row that may fall outside the range before giving up on one
+const QUASI_TRIES: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sampling {
+    #[default]
+    Uniform,
+    Stratified,
+    Quasi,
+}
+
+impl Sampling {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "uniform" => Some(Sampling::Uniform),
+            "stratified" | "residue" => Some(Sampling::Stratified),
+            "quasi" | "r2" => Some(Sampling::Quasi),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sampling::Uniform => "uniform",
+            Sampling::Stratified => "stratified",
+            Sampling::Quasi => "quasi",
+        }
+    }
+}
+
+pub struct Sampler {
+    kind: Sampling,
+    low: BigUint,
+    high: BigUint, // inclusive
+    k: u32,
+    classes: Vec<u64>, // stratified: surviving residues that have values in range
+    next_class: usize,
+    point: (u64, u64), // quasi: current R2 point
+}
+
+impl Sampler {
+    /// Sampler over [low, high]; `k` sets the residue classes mod 2^k
+    pub fn new(kind: Sampling, k: u32, low: &BigUint, high: &BigUint, rng: &mut Rng) -> Self {
+        let mut s = Sampler {
+            kind, low: low.clone(), high: high.clone(), k,
+            classes: Vec::new(), next_class: 0, point: (rng.next_u64(), rng.next_u64()),
+        };
+        if kind == Sampling::Stratified {
+            s.classes = sieve(k).iter().enumerate()
+                .filter(|&(r, &survives)| survives && s.high_part_range(r as u64).is_some())
+                .map(|(r, _)| r as u64)
+                .collect();
+            s.next_class = (rng.next_u64() % s.classes.len().max(1) as u64) as usize;
+        }
+        s
+    }
+
+    /// Next start to test
+    pub fn next(&mut self, rng: &mut Rng) -> BigUint {
+        match self.kind {
+            Sampling::Stratified if !self.classes.is_empty() => {
+                let r = self.classes[self.next_class];
+                self.next_class = (self.next_class + 1) % self.classes.len();
+                let (lo, hi) = self.high_part_range(r).expect("classes keep only residues with values in range");
+                (rng.gen_range_biguint(&lo, &hi) << self.k) + r
+            }
+            Sampling::Quasi => {
+                for _ in 0..QUASI_TRIES {
+                    self.point = (self.point.0.wrapping_add(R2_STEP.0), self.point.1.wrapping_add(R2_STEP.1));
+                    let n = self.quasi_value(rng);
+                    if n >= self.low && n <= self.high { return n; }
+                }
+                rng.gen_range_biguint(&self.low, &self.high)
+            }
+            _ => rng.gen_range_biguint(&self.low, &self.high),
+        }
+    }
+
+    /// Range of m with m·2^k + r in [low, high], if any
+    fn high_part_range(&self, r: u64) -> Option<(BigUint, BigUint)> {
+        let r = BigUint::from(r);
+        if r > self.high { return None; }
+        let lo = if self.low > r { ((&self.low - &r - 1u32) >> self.k) + 1u32 } else { BigUint::zero() };
+        let hi = (&self.high - &r) >> self.k;
+        (lo <= hi).then_some((lo, hi))
+    }
+
+    /// The value at the current quasi point: the first coordinate picks the bit
+    /// length, the second the 64 bits after the leading one
+    fn quasi_value(&self, rng: &mut Rng) -> BigUint {
+        let (bmin, bmax) = (self.low.bits().max(1), self.high.bits());
+        let b = bmin + ((self.point.0 as u128 * (bmax - bmin + 1) as u128) >> 64) as u64;
+        let f = b - 1; // bits below the leading one
+        let top = BigUint::one() << f;
+        if f <= 64 {
+            return top + (self.point.1.checked_shr((64 - f) as u32).unwrap_or(0));
+        }
+        let rest = rng.gen_range_biguint(&BigUint::zero(), &((BigUint::one() << (f - 64)) - 1u32));
+        top + (BigUint::from(self.point.1) << (f - 64)) + rest
+    }
+}
+
+/// What a run's samples covered: residue classes mod 2^k and bands of bit length
+pub struct Coverage {
+    k: u32,
+    survivor: Vec<bool>,
+    residues: Vec<u64>,
+    bits: (u64, u64), // bit-length range of the sampled interval
+    bands: Vec<u64>,
+    samples: u64,
+}
+
+impl Coverage {
+    pub fn new(k: u32, low: &BigUint, high: &BigUint) -> Self {
+        let bits = (low.bits().max(1), high.bits().max(1));
+        let bands = BANDS.min(bits.1 - bits.0 + 1) as usize;
+        Coverage { k, survivor: sieve(k), residues: vec![0; 1 << k], bits, bands: vec![0; bands], samples: 0 }
+    }
+
+    pub fn record(&mut self, n: &BigUint) {
+        let mask = (1u64 << self.k) - 1;
+        let r = n.iter_u64_digits().next().unwrap_or(0) & mask;
+        self.residues[r as usize] += 1;
+        let b = n.bits().clamp(self.bits.0, self.bits.1) - self.bits.0;
+        let band = b * self.bands.len() as u64 / (self.bits.1 - self.bits.0 + 1);
+        self.bands[band as usize] += 1;
+        self.samples += 1;
+    }
+
+    pub fn samples(&self) -> u64 { self.samples }
+
+    /// Summary for the end-of-run report
+    pub fn report(&self) -> String {
+        // Classes hit, and the fewest and most samples any one of them got
+        let spread = |counts: &mut dyn Iterator<Item = u64>| {
+            let (mut hit, mut total, mut min, mut max) = (0, 0, u64::MAX, 0);
+            for c in counts {
+                total += 1;
+                hit += (c > 0) as u64;
+                (min, max) = (min.min(c), max.max(c));
+            }
+            format!("{hit}/{total} hit, {}-{max} samples each", if total == 0 { 0 } else { min })
+        };
+        let survivors = spread(&mut self.residues.iter().zip(&self.survivor).filter(|&(_, &s)| s).map(|(&c, _)| c));
+        let others = spread(&mut self.residues.iter().zip(&self.survivor).filter(|&(_, &s)| !s).map(|(&c, _)| c));
+        let bands = spread(&mut self.bands.iter().copied());
+        format!(
+            "coverage: {} samples; residues mod 2^{}: surviving classes {survivors}, other classes {others}; bit lengths {}..{} in {} bands: {bands}",
+            self.samples, self.k, self.bits.0, self.bits.1, self.bands.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random_range;
+
+    #[test]
+    fn stratified_hits_every_surviving_class_evenly() {
+        let survivor = sieve(10);
+        let mut rng = Rng::from_seed(50);
+        for (low, high) in [random_range(), (BigUint::from(5u32), BigUint::from(3000u32))] {
+            let mut s = Sampler::new(Sampling::Stratified, 10, &low, &high, &mut rng);
+            let classes = s.classes.len();
+            assert!(classes > 0);
+            let mut counts = vec![0u32; 1 << 10];
+            for _ in 0..3 * classes {
+                let n = s.next(&mut rng);
+                assert!(low <= n && n <= high, "{n} outside [{low}, {high}]");
+                counts[(n.iter_u64_digits().next().unwrap() & 1023) as usize] += 1;
+            }
+            for (r, &c) in counts.iter().enumerate() {
+                let expected = if s.classes.contains(&(r as u64)) { 3 } else { 0 };
+                assert_eq!(c, expected, "class {r}");
+                if c > 0 { assert!(survivor[r]); }
+            }
+        }
+    }
+
+    #[test]
+    fn quasi_spreads_bit_lengths_evenly() {
+        let (low, high) = random_range();
+        let mut rng = Rng::from_seed(51);
+        let mut s = Sampler::new(Sampling::Quasi, 10, &low, &high, &mut rng);
+        let mut cov = Coverage::new(10, &low, &high);
+        for _ in 0..32 * 50 {
+            let n = s.next(&mut rng);
+            assert!(low <= n && n <= high);
+            cov.record(&n);
+        }
+        // A low-discrepancy sequence lands within a few samples of even per band;
+        // uniform sampling would put nearly all of them in the top one
+        for (i, &c) in cov.bands.iter().enumerate() {
+            assert!((45..=55).contains(&c), "band {i} got {c} samples");
+        }
+    }
+
+    #[test]
+    fn coverage_report_counts_classes_and_bands() {
+        let (low, high) = (BigUint::from(16u32), BigUint::from(255u32));
+        let mut cov = Coverage::new(2, &low, &high);
+        for n in [16u32, 17, 19, 255] { cov.record(&BigUint::from(n)); }
+        // mod 4 only class 3 survives; bit lengths 5..8 make 4 bands
+        assert_eq!(
+            cov.report(),
+            "coverage: 4 samples; residues mod 2^2: surviving classes 1/1 hit, 2-2 samples each, \
+             other classes 2/3 hit, 0-1 samples each; bit lengths 5..8 in 4 bands: 2/4 hit, 0-3 samples each"
+        );
+    }
+}
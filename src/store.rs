@@ -0,0 +1,375 @@
+// ---------- Results database: scanned ranges, records and findings ----------
+//
+// A single append-only text journal, one entry per line, so a crash can lose
+// at most the line being written (dropped on the next open) and the file stays greppable:
+//
+//   range <start> <processed> <reaches_one> <nontrivial_cycle> <steps_overflow> <max_steps> <digest> <unix_ts>
+//   record steps <value> <start> <unix_ts>
+//   record peak_bits <value> <start> <unix_ts>
+//   finding <kind> <start> <unix_ts>
+//
+// Completed ranges are merged into disjoint intervals as they are inserted
+// (and again when the journal is loaded), which is what coverage questions need.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+use crate::{Classified, short_decimal};
+use crate::digest::RangeDigest;
+use crate::dist::UnitResult;
+
+const HEADER: &str = "# collatz results v1";
+
+/// Best values seen so far, with the start that produced them
+#[derive(Debug, Clone, Default)]
+pub struct Records {
+    pub steps: Option<(u64, BigUint)>,
+    pub peak_bits: Option<(u64, BigUint)>,
+}
+
+pub struct Store {
+    path: PathBuf,
+    file: File,
+    // Disjoint, non-adjacent half-open intervals [start, end), keyed by start
+    intervals: BTreeMap<BigUint, BigUint>,
+    ranges: u64,
+    pub records: Records,
+    pub findings: Vec<(String, BigUint)>,
+}
+
+fn now_ts() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn bad_line(path: &Path, no: usize, why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}:{no}: {why}", path.display()))
+}
+
+impl Store {
+    /// Open (or create) a results journal and load everything in it
+    pub fn open(path: &Path) -> io::Result<Store> {
+        let mut store = Store {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).read(true).open(path)?,
+            intervals: BTreeMap::new(),
+            ranges: 0,
+            records: Records::default(),
+            findings: Vec::new(),
+        };
+        let mut reader = BufReader::new(File::open(path)?);
+        let (mut line, mut no, mut len) = (String::new(), 0, 0u64);
+        loop {
+            line.clear();
+            let n = reader.read_line(&mut line)?;
+            if n == 0 { break; }
+            no += 1;
+            let Some(entry) = line.strip_suffix('\n') else {
+                // A write cut short by a crash or a full disk. Even if it parses, a
+                // truncated number would be wrong, so drop it before appending again
+                eprintln!("warning: {}:{no}: dropping unterminated last line '{line}'", path.display());
+                store.file.set_len(len)?;
+                break;
+            };
+            len += n as u64;
+            if entry.is_empty() || entry.starts_with('#') { continue; }
+            store.load_line(entry).map_err(|why| bad_line(path, no, &why))?;
+        }
+        if len == 0 {
+            writeln!(store.file, "{HEADER}")?;
+        }
+        Ok(store)
+    }
+
+    fn load_line(&mut self, line: &str) -> Result<(), String> {
+        let f: Vec<&str> = line.split_whitespace().collect();
+        let big = |s: &str| s.parse::<BigUint>().map_err(|_| format!("bad number '{s}'"));
+        let num = |s: &str| s.parse::<u64>().map_err(|_| format!("bad number '{s}'"));
+        match f.as_slice() {
+            ["range", start, processed, ..] => {
+                let start = big(start)?;
+                let end = &start + num(processed)?;
+                self.merge(start, end);
+                self.ranges += 1;
+            }
+            ["record", "steps", v, start, ..] => { self.offer_steps(num(v)?, big(start)?); }
+            ["record", "peak_bits", v, start, ..] => { self.offer_peak(num(v)?, big(start)?); }
+            ["finding", kind, start, ..] => self.findings.push((kind.to_string(), big(start)?)),
+            _ => return Err(format!("unrecognised entry: {line}")),
+        }
+        Ok(())
+    }
+
+    /// Insert [start, end) and coalesce it with any overlapping or touching intervals
+    fn merge(&mut self, mut start: BigUint, mut end: BigUint) {
+        if end <= start { return; }
+        // A predecessor that reaches our start swallows us
+        if let Some((s, e)) = self.intervals.range(..=&start).next_back()
+            && *e >= start
+        {
+            start = s.clone();
+            if *e > end { end = e.clone(); }
+        }
+        // Absorb everything that begins inside (or right at the end of) the new interval
+        let absorbed: Vec<BigUint> = self
+            .intervals
+            .range((Bound::Included(&start), Bound::Included(&end)))
+            .map(|(s, _)| s.clone())
+            .collect();
+        for s in absorbed {
+            if let Some(e) = self.intervals.remove(&s) && e > end { end = e; }
+        }
+        self.intervals.insert(start, end);
+    }
+
+    fn offer_steps(&mut self, v: u64, start: BigUint) -> bool {
+        if self.records.steps.as_ref().is_some_and(|(best, _)| *best >= v) { return false; }
+        self.records.steps = Some((v, start));
+        true
+    }
+
+    fn offer_peak(&mut self, v: u64, start: BigUint) -> bool {
+        if self.records.peak_bits.as_ref().is_some_and(|(best, _)| *best >= v) { return false; }
+        self.records.peak_bits = Some((v, start));
+        true
+    }
+
+    /// Update records from a batch; only improvements are journaled
+    fn note_records(&mut self, res: &UnitResult) -> io::Result<()> {
+        let ts = now_ts();
+        if let Some(ref s) = res.max_steps_start && self.offer_steps(res.max_steps, s.clone()) {
+            writeln!(self.file, "record steps {} {s} {ts}", res.max_steps)?;
+        }
+        if let Some(ref s) = res.peak_start && self.offer_peak(res.peak_bits, s.clone()) {
+            writeln!(self.file, "record peak_bits {} {s} {ts}", res.peak_bits)?;
+        }
+        Ok(())
+    }
+
+    /// Record a fully scanned range starting at `start` (covering `res.processed` starts)
+    pub fn add_range(&mut self, start: &BigUint, res: &UnitResult) -> io::Result<()> {
+        if res.processed == 0 { return Ok(()); }
+        writeln!(
+            self.file,
+            "range {start} {} {} {} {} {} {} {}",
+            res.processed, res.reaches_one, res.nontrivial_cycle, res.steps_overflow, res.max_steps, res.digest, now_ts()
+        )?;
+        self.merge(start.clone(), start + res.processed);
+        self.ranges += 1;
+        self.note_records(res)?;
+        self.file.flush()
+    }
+
+    /// Random samples do not add coverage but can still set records
+    pub fn add_samples(&mut self, res: &UnitResult) -> io::Result<()> {
+        self.note_records(res)?;
+        self.file.flush()
+    }
+
+    pub fn add_finding(&mut self, kind: &str, start: &BigUint) -> io::Result<()> {
+        writeln!(self.file, "finding {kind} {start} {}", now_ts())?;
+        self.findings.push((kind.to_string(), start.clone()));
+        // Findings are rare and precious; make sure they hit the disk
+        self.file.sync_all()
+    }
+
+    /// Largest n with every start in [1, n] verified, if start 1 has been
+    fn contiguous_bound(&self) -> Option<BigUint> {
+        let (first, end) = self.intervals.iter().next()?;
+        (*first <= BigUint::from(1u32)).then(|| end - 1u32)
+    }
+
+    /// Unverified stretches above 0, lowest first
+    fn gaps(&self) -> Vec<(BigUint, BigUint)> {
+        let one = BigUint::from(1u32);
+        let leading = self.intervals.keys().next().filter(|&first| *first > one).map(|first| (one, first.clone()));
+        let between = self.intervals.values().zip(self.intervals.keys().skip(1)).map(|(e, s)| (e.clone(), s.clone()));
+        leading.into_iter().chain(between).collect()
+    }
+
+    /// Coverage, gaps, records and findings
+    pub fn write_status(&self, out: &mut impl Write, max_gaps: usize) -> io::Result<()> {
+        writeln!(out, "database: {}", self.path.display())?;
+        let total = self.intervals.iter().fold(BigUint::zero(), |acc, (s, e)| acc + (e - s));
+        writeln!(out, "verified starts: {total} ({} ranges, {} merged intervals)", self.ranges, self.intervals.len())?;
+        match self.contiguous_bound() {
+            Some(bound) => writeln!(out, "highest contiguous verified bound: {bound} (all of [1, {bound}] verified)")?,
+            None => writeln!(out, "highest contiguous verified bound: none (start 1 not verified)")?,
+        }
+        let gaps = self.gaps();
+        writeln!(out, "coverage gaps: {}", gaps.len())?;
+        for (from, to) in gaps.iter().take(max_gaps) {
+            writeln!(out, "  [{from}, {to}) ({} starts)", to - from)?;
+        }
+        if gaps.len() > max_gaps {
+            writeln!(out, "  ... {} more", gaps.len() - max_gaps)?;
+        }
+        match self.records.steps {
+            Some((v, ref s)) => writeln!(out, "record stopping time: {v} steps (start {})", short_decimal(s, 20, 20))?,
+            None => writeln!(out, "record stopping time: none")?,
+        }
+        match self.records.peak_bits {
+            Some((v, ref s)) => writeln!(out, "record peak: {v} bits (start {})", short_decimal(s, 20, 20))?,
+            None => writeln!(out, "record peak: none")?,
+        }
+        writeln!(out, "findings: {}", self.findings.len())?;
+        for (kind, start) in &self.findings {
+            writeln!(out, "  {kind} {start}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts classified since the last flush. A batch with a `start` is a
+/// contiguous sequential segment; without one it is a bag of random samples.
+pub struct Batch {
+    start: Option<BigUint>,
+    res: UnitResult,
+    digest: RangeDigest,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch { start: None, res: UnitResult::default(), digest: RangeDigest::new() }
+    }
+
+    pub fn len(&self) -> u64 { self.res.processed }
+
+    pub fn record(&mut self, start: &BigUint, sequential: bool, c: &Classified) {
+        if sequential && self.start.is_none() { self.start = Some(start.clone()); }
+        self.digest.update(c);
+        self.res.record(start, c);
+    }
+}
+
+impl Store {
+    /// Journal a batch (as a range or as samples) and empty it
+    pub fn flush(&mut self, batch: &mut Batch) -> io::Result<()> {
+        if batch.len() > 0 {
+            batch.res.digest = batch.digest.hex();
+            match batch.start {
+                Some(ref start) => self.add_range(start, &batch.res)?,
+                None => self.add_samples(&batch.res)?,
+            }
+        }
+        *batch = Batch::new();
+        Ok(())
+    }
+}
+
+/// `status --db <PATH> [--gaps N]`
+pub fn status_main(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut db = String::from("collatz.db");
+    let mut max_gaps = 20usize;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => db = args.next().ok_or("--db needs a value")?,
+            "--gaps" => {
+                let v = args.next().ok_or("--gaps needs a value")?;
+                max_gaps = crate::numexpr::parse_u64(&v).map_err(|e| format!("--gaps: {e}"))? as usize;
+            }
+            other => return Err(format!("unknown status option: {other}").into()),
+        }
+    }
+    if !Path::new(&db).exists() {
+        return Err(format!("no results database at {db}").into());
+    }
+    Store::open(Path::new(&db))?.write_status(&mut io::stdout().lock(), max_gaps)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store over a fresh journal holding `lines`
+    fn store(name: &str, lines: &str) -> Store {
+        let path = std::env::temp_dir().join(format!("collatz-store-{name}-{}.db", std::process::id()));
+        std::fs::write(&path, lines).unwrap();
+        let store = Store::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        store
+    }
+
+    fn intervals(store: &Store) -> Vec<(u32, u32)> {
+        let small = |n: &BigUint| n.to_string().parse().unwrap();
+        store.intervals.iter().map(|(s, e)| (small(s), small(e))).collect()
+    }
+
+    fn add(st: &mut Store, start: u32, end: u32) { st.merge(BigUint::from(start), BigUint::from(end)) }
+
+    #[test]
+    fn merge_coalesces_overlaps_duplicates_and_neighbours() {
+        let mut st = store("merge", "");
+        add(&mut st, 100, 200);
+        add(&mut st, 300, 400);
+        add(&mut st, 100, 200); // duplicate
+        add(&mut st, 150, 180); // contained
+        add(&mut st, 500, 500); // empty
+        assert_eq!(intervals(&st), [(100, 200), (300, 400)]);
+        add(&mut st, 200, 250); // touches the end
+        add(&mut st, 50, 120); // overlaps the start
+        assert_eq!(intervals(&st), [(50, 250), (300, 400)]);
+        add(&mut st, 240, 300); // bridges the gap
+        assert_eq!(intervals(&st), [(50, 400)]);
+        add(&mut st, 10, 1000); // swallows everything
+        add(&mut st, 2000, 3000);
+        assert_eq!(intervals(&st), [(10, 1000), (2000, 3000)]);
+    }
+
+    #[test]
+    fn journal_ranges_are_merged_on_load() {
+        let st = store("load", "# collatz results v1\nrange 1 10 10 0 0 19 d 0\nrange 11 10 10 0 0 19 d 0\nrange 5 3 3 0 0 19 d 0\n");
+        assert_eq!(intervals(&st), [(1, 21)]);
+        assert_eq!(st.ranges, 3);
+    }
+
+    #[test]
+    fn a_torn_last_line_is_dropped_before_the_next_append() {
+        let path = std::env::temp_dir().join(format!("collatz-store-torn-{}.db", std::process::id()));
+        let good = "# collatz results v1\nrange 1 10 10 0 0 19 d 0\n";
+        std::fs::write(&path, format!("{good}range 12")).unwrap();
+        let mut st = Store::open(&path).unwrap();
+        assert_eq!(intervals(&st), [(1, 11)]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), good);
+        st.add_finding("NONTRIVIAL_CYCLE_START", &BigUint::from(7u32)).unwrap();
+        drop(st);
+        let st = Store::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(intervals(&st), [(1, 11)]);
+        assert_eq!(st.findings, [("NONTRIVIAL_CYCLE_START".to_string(), BigUint::from(7u32))]);
+
+        // Nothing but a torn header: the file starts over
+        std::fs::write(&path, "# collatz res").unwrap();
+        drop(Store::open(&path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# collatz results v1\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn status_measures_the_contiguous_bound_from_one() {
+        let status = |st: &Store| {
+            let mut out = Vec::new();
+            st.write_status(&mut out, 20).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let st = store("status-gap", "range 1000 1000 1000 0 0 178 d 0\n");
+        let text = status(&st);
+        assert!(text.contains("verified starts: 1000 (1 ranges, 1 merged intervals)"), "{text}");
+        assert!(text.contains("highest contiguous verified bound: none (start 1 not verified)"), "{text}");
+        assert!(text.contains("coverage gaps: 1\n  [1, 1000) (999 starts)\n"), "{text}");
+
+        let st = store("status-ok", "range 1 100 100 0 0 118 d 0\nrange 201 50 50 0 0 127 d 0\nrecord steps 118 97 0\n");
+        let text = status(&st);
+        assert!(text.contains("highest contiguous verified bound: 100 (all of [1, 100] verified)"), "{text}");
+        assert!(text.contains("coverage gaps: 1\n  [101, 201) (100 starts)\n"), "{text}");
+        assert!(text.contains("record stopping time: 118 steps (start 97)"), "{text}");
+        assert!(text.contains("record peak: none\nfindings: 0\n"), "{text}");
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use num_bigint::BigUint;
+use minifb::{Window, WindowOptions, Key, KeyRepeat};
+
+use crate::font::{SMALL_FONT_H, draw_text_block, draw_text_small, draw_text_small_scaled, text_block_size};
+use crate::backend::{Backend, Num};
+use crate::maps::StepMap;
+use crate::rng::{RandomSource, Rng};
+use crate::{Outcome, random_range, short_decimal};
+
+// ---------- Visualization (minifb) ----------
+
+// Snapshot of the compute thread's state, shown in the HUD
+#[derive(Debug, Clone, Copy)]
+pub struct RunStatus {
+    pub processed: u64,
+    pub sps: f64,
+    pub paused: bool,
+    pub random: bool,
+    pub viz_interval: u64,
+}
+
+// Messages from compute thread to visualization thread
+pub enum VizMsg {
+    Draw { start: BigUint, outcome: Outcome },
+    Stats(RunStatus),
+}
+
+// Commands from visualization thread back to compute thread
+pub enum VizCmd {
+    TogglePause,
+    DrawNext,      // send the next tested start right away instead of waiting for the interval
+    ToggleRandom,
+    SetVizInterval(u64),
+}
+
+const VIZ_W: usize = 500;
+const VIZ_H: usize = 500;
+/// How many recently tested starts can be stepped back through
+const HISTORY_LEN: usize = 32;
+
+pub fn run_viz(rx: Receiver<VizMsg>, cmd_tx: Sender<VizCmd>, max_steps: usize, viz_interval: u64) {
+    let mut window = match Window::new(
+        "Collatz Visualizer",
+        VIZ_W,
+        VIZ_H,
+        WindowOptions {
+            resize: false,
+            scale: minifb::Scale::X1,
+            ..WindowOptions::default()
+        },
+    ) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("viz error: {e}");
+            return;
+        }
+    };
+
+    let mut buffer = vec![0u32; VIZ_W * VIZ_H];
+    // Trajectory currently on screen; only ever a start the compute thread sent us,
+    // unless idle demo mode is switched on
+    let mut current: Option<Trajectory> = None;
+    let mut history = History::new();
+    let mut demo = false;
+    let mut latest_stats: Option<RunStatus> = None;
+    // Last interval we know of; updated optimistically on +/- and confirmed by Stats
+    let mut viz_interval = viz_interval.max(1);
+    // Transient message (e.g. screenshot path) shown in the HUD for a few seconds
+    let mut notice: Option<(String, Instant)> = None;
+    let max_points = max_steps.max(1);
+    let steps_per_tick: usize = (max_points / 60).clamp(1, 2000);
+    // Local RNG used only by the idle demo; its samples are never labelled as tested
+    let mut vrng = Rng::seeded();
+    let (rand_low, rand_high_inclusive) = random_range();
+    
+    // Initial clear
+    clear_buffer(&mut buffer, 0xFFFFFFFF);
+    draw_grid(&mut buffer, 50, 0xFFE0E0E0);
+    draw_axes(&mut buffer, 10, 0xFF000000);
+    window.set_title("Collatz Visualizer - waiting for samples...");
+    let _ = window.update_with_buffer(&buffer, VIZ_W, VIZ_H);
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let mut should_redraw = false;
+        
+        // Keyboard controls; commands go back to the compute thread
+        for key in window.get_keys_pressed(KeyRepeat::No) {
+            let cmd = match key {
+                Key::Space => Some(VizCmd::TogglePause),
+                Key::N => Some(VizCmd::DrawNext),
+                Key::R => Some(VizCmd::ToggleRandom),
+                Key::Equal | Key::NumPadPlus => {
+                    viz_interval = viz_interval.saturating_mul(2);
+                    Some(VizCmd::SetVizInterval(viz_interval))
+                }
+                Key::Minus | Key::NumPadMinus => {
+                    viz_interval = (viz_interval / 2).max(1);
+                    Some(VizCmd::SetVizInterval(viz_interval))
+                }
+                Key::P => {
+                    let text = match save_screenshot(&buffer) {
+                        Ok(path) => format!("saved {path}"),
+                        Err(e) => format!("screenshot failed: {e}"),
+                    };
+                    eprintln!("{text}");
+                    notice = Some((text, Instant::now()));
+                    should_redraw = true;
+                    None
+                }
+                Key::Left if !history.entries.is_empty() => {
+                    // Step back through previously tested starts
+                    demo = false;
+                    current = history.older().map(Trajectory::tested);
+                    None
+                }
+                Key::Right if history.view.is_some() => {
+                    current = history.newer().map(Trajectory::tested);
+                    None
+                }
+                Key::D => {
+                    demo = !demo;
+                    history.view = None;
+                    current = if demo { None } else { history.newest().map(Trajectory::tested) };
+                    should_redraw = true;
+                    None
+                }
+                _ => None,
+            };
+            if let Some(cmd) = cmd { let _ = cmd_tx.send(cmd); }
+        }
+        if let Some((_, at)) = notice && at.elapsed() >= Duration::from_secs(3) {
+            notice = None;
+            should_redraw = true;
+        }
+
+        // Check for new messages
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                VizMsg::Draw { start, outcome } => {
+                    if !history.push(start, outcome) { continue; }
+                    if history.view.is_none() && !demo {
+                        // Begin animating this trajectory from scratch
+                        current = history.newest().map(Trajectory::tested);
+                        should_redraw = true;
+                    }
+                }
+                VizMsg::Stats(status) => {
+                    viz_interval = status.viz_interval;
+                    latest_stats = Some(status);
+                    should_redraw = true;
+                }
+            }
+        }
+
+        // Idle demo: keep the animation moving with local samples, clearly marked as untested
+        if demo && current.as_ref().is_none_or(|t| t.done) {
+            current = Some(Trajectory::new(vrng.gen_range_biguint(&rand_low, &rand_high_inclusive), None));
+        }
+
+        // Incrementally extend trajectory for animation
+        if let Some(ref mut traj) = current && !traj.done {
+            traj.advance(steps_per_tick, max_points);
+            should_redraw = true;
+        }
+
+        // Only redraw when we have new data
+        if should_redraw && let Some(ref traj) = current && traj.bits_window.len() >= 2 {
+            // Draw seed label bottom-left with heading
+            let heading = match history.view {
+                _ if traj.outcome.is_none() => "IDLE DEMO - NOT TESTED:".to_string(),
+                Some(i) => format!("HISTORY {}/{} - TESTED:", i + 1, history.entries.len()),
+                None => "NUMBER BEING TESTED:".to_string(),
+            };
+
+            // Stats HUD top-right on a plain backdrop so the line stays readable underneath
+            let mut hud = String::new();
+            if let Some(st) = latest_stats {
+                let mode = if st.random { "random" } else { "sequential" };
+                let state = if st.paused { " (paused)" } else { "" };
+                hud.push_str(&format!("processed: {}\nrate: {:.1} samples/s\nmode: {mode}{state}\n", st.processed, st.sps));
+            }
+            hud.push_str(&format!("draw every: {viz_interval}\n"));
+            let outcome = match traj.outcome {
+                Some(Outcome::ReachesOne) => "reaches 1",
+                Some(Outcome::NontrivialCycle) => "nontrivial cycle",
+                Some(Outcome::StepsOverflow) => "step overflow",
+                None => "untested (demo)",
+            };
+            let at_one = if traj.done { " (at 1)" } else { "" };
+            hud.push_str(&format!("steps: {}{at_one}\npeak: {} bits\noutcome: {outcome}\n", traj.steps, traj.peak_bits));
+            hud.push_str("\n[space] pause  [n] next\n[r] mode  [+/-] interval\n[</>] history  [d] demo\n[p] screenshot");
+            if let Some((ref text, _)) = notice {
+                hud.push_str(&format!("\n{text}"));
+            }
+
+            draw_frame(&mut buffer, traj, &heading, &hud);
+            let _ = window.update_with_buffer(&buffer, VIZ_W, VIZ_H);
+        } else {
+            window.update();
+        }
+        
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Render one complete frame: grid, the trajectory's bit-length line, its label and the HUD
+fn draw_frame(buffer: &mut [u32], traj: &Trajectory, heading: &str, hud: &str) {
+    clear_buffer(buffer, 0xFFFFFFFF);
+    draw_grid(buffer, 50, 0xFFE0E0E0);
+    draw_axes(buffer, 10, 0xFF000000);
+
+    // Draw the visible window; demo samples use a different colour
+    let line_color = if traj.outcome.is_some() { 0xFF000000 } else { 0xFF3A6FD8 };
+    let pad = 10usize;
+    let w = VIZ_W - 2*pad;
+    let h = VIZ_H - 2*pad;
+    let bits_window = &traj.bits_window;
+    let len = bits_window.len();
+    let max_bits = *bits_window.iter().max().unwrap_or(&1);
+    let mut prev = point_xy(0, bits_window[0], len, max_bits, w, h, pad);
+    for (i, bits) in bits_window.iter().enumerate().skip(1) {
+        let curr = point_xy(i, *bits, len, max_bits, w, h, pad);
+        draw_line(prev.0 as i32, prev.1 as i32, curr.0 as i32, curr.1 as i32, line_color, buffer);
+        prev = curr;
+    }
+
+    let scale_num: i32 = 2; // make number larger
+    let num_h = (SMALL_FONT_H as i32) * scale_num;
+    let gap = 4;
+    let num_y = (VIZ_H as i32) - 10 - num_h;
+    let heading_y = (num_y - gap - SMALL_FONT_H as i32).max(0);
+    draw_text_small(buffer, 12, heading_y, heading, line_color);
+    draw_text_small_scaled(buffer, 12, num_y, &traj.label, line_color, scale_num as usize);
+
+    let (hud_w, hud_h) = text_block_size(hud, 1);
+    let hud_x = VIZ_W.saturating_sub(hud_w + 20);
+    fill_rect(buffer, hud_x.saturating_sub(4), 12, hud_w + 8, hud_h + 8, 0xFFF4F4F4);
+    draw_text_block(buffer, hud_x as i32, 16, hud, 0xFF000000, 1);
+}
+
+/// A frame drawn without a window, for the benchmark harness. The trajectory is
+/// prepared up front so `draw` times rendering alone.
+pub struct OffscreenFrame {
+    buffer: Vec<u32>,
+    traj: Trajectory,
+    hud: String,
+}
+
+impl OffscreenFrame {
+    pub fn new(start: BigUint, max_steps: usize) -> Self {
+        let mut traj = Trajectory::new(start, Some(Outcome::ReachesOne));
+        traj.advance(max_steps, max_steps.max(2));
+        let hud = format!(
+            "processed: 123456\nrate: 9999.9 samples/s\nmode: random\ndraw every: 1000\nsteps: {}\npeak: {} bits\noutcome: reaches 1\n\n[space] pause  [n] next\n[r] mode  [+/-] interval\n[</>] history  [d] demo\n[p] screenshot",
+            traj.steps, traj.peak_bits
+        );
+        OffscreenFrame { buffer: vec![0u32; VIZ_W * VIZ_H], traj, hud }
+    }
+
+    pub fn draw(&mut self) -> &[u32] {
+        draw_frame(&mut self.buffer, &self.traj, "NUMBER BEING TESTED:", &self.hud);
+        &self.buffer
+    }
+}
+
+/// Recently tested starts with their outcomes, oldest first, and which one is on screen
+struct History {
+    entries: VecDeque<(BigUint, Outcome)>,
+    // None follows the newest tested start; Some(i) pins entries[i]
+    view: Option<usize>,
+}
+
+impl History {
+    fn new() -> Self { History { entries: VecDeque::with_capacity(HISTORY_LEN), view: None } }
+
+    /// Add a tested start; false if it repeats the newest one (the final start may
+    /// be resent when a run finishes). Evicting the pinned entry unpins the view
+    /// instead of sliding it onto whichever start took that slot.
+    fn push(&mut self, start: BigUint, outcome: Outcome) -> bool {
+        if self.entries.back().is_some_and(|(n, _)| *n == start) { return false; }
+        if self.entries.len() == HISTORY_LEN {
+            self.entries.pop_front();
+            self.view = self.view.and_then(|i| i.checked_sub(1));
+        }
+        self.entries.push_back((start, outcome));
+        true
+    }
+
+    /// Pin the entry before the one on screen and return it
+    fn older(&mut self) -> Option<&(BigUint, Outcome)> {
+        let idx = self.view.or(self.entries.len().checked_sub(1))?.saturating_sub(1);
+        self.view = Some(idx);
+        self.entries.get(idx)
+    }
+
+    /// The next newer entry; stepping onto the newest returns to live
+    fn newer(&mut self) -> Option<&(BigUint, Outcome)> {
+        let idx = self.view? + 1;
+        self.view = (idx + 1 < self.entries.len()).then_some(idx);
+        self.entries.get(idx.min(self.entries.len() - 1))
+    }
+
+    fn newest(&self) -> Option<&(BigUint, Outcome)> { self.entries.back() }
+}
+
+// One trajectory being animated, extended a few steps per frame
+struct Trajectory {
+    n: Num, // stepped in place, one standard step per plotted point
+    label: String,
+    outcome: Option<Outcome>, // None for idle-demo samples the search never evaluated
+    bits_window: VecDeque<usize>,
+    steps: u64,
+    peak_bits: usize,
+    done: bool,
+}
+
+impl Trajectory {
+    fn new(start: BigUint, outcome: Option<Outcome>) -> Self {
+        Trajectory {
+            label: short_decimal(&start, 12, 12),
+            n: Num::from_biguint(&start),
+            outcome,
+            bits_window: VecDeque::new(),
+            steps: 0,
+            peak_bits: 0,
+            done: false,
+        }
+    }
+
+    fn tested(entry: &(BigUint, Outcome)) -> Self {
+        Trajectory::new(entry.0.clone(), Some(entry.1))
+    }
+
+    fn advance(&mut self, steps: usize, max_points: usize) {
+        for _ in 0..steps {
+            // Record current magnitude
+            let bits = (self.n.bits() as usize).max(1);
+            self.peak_bits = self.peak_bits.max(bits);
+            self.bits_window.push_back(bits);
+            if self.bits_window.len() > max_points { self.bits_window.pop_front(); }
+            // Advance
+            if self.n.is_one() { self.done = true; break; }
+            self.n.step(StepMap::Standard);
+            self.steps += 1;
+        }
+    }
+}
+
+/// Write the framebuffer as a binary PPM in the working directory, returning its file name.
+fn save_screenshot(buf: &[u32]) -> std::io::Result<String> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let name = format!("collatz-{stamp}.ppm");
+    let mut w = BufWriter::new(File::create(&name)?);
+    write!(w, "P6\n{VIZ_W} {VIZ_H}\n255\n")?;
+    for px in buf {
+        w.write_all(&[(px >> 16) as u8, (px >> 8) as u8, *px as u8])?;
+    }
+    w.flush()?;
+    Ok(name)
+}
+
+fn clear_buffer(buf: &mut [u32], color: u32) {
+    for px in buf.iter_mut() { *px = color; }
+}
+
+fn fill_rect(buf: &mut [u32], x: usize, y: usize, w: usize, h: usize, color: u32) {
+    for yy in y..(y + h).min(VIZ_H) {
+        for xx in x..(x + w).min(VIZ_W) { buf[yy * VIZ_W + xx] = color; }
+    }
+}
+
+// streaming visualization no longer uses a precomputed trajectory function
+
+fn point_xy(i: usize, bits: usize, len: usize, max_bits: usize, w: usize, h: usize, pad: usize) -> (usize, usize) {
+    let x = pad + (i.saturating_mul(w.saturating_sub(1))) / (len.saturating_sub(1).max(1));
+    // y: top is 0; map higher bits to lower y (higher on screen)
+    let y = pad + (h.saturating_sub(1)).saturating_sub((bits.saturating_mul(h.saturating_sub(1))) / max_bits.max(1));
+    (x.min(VIZ_W.saturating_sub(1)), y.min(VIZ_H.saturating_sub(1)))
+}
+
+fn draw_line(x0: i32, y0: i32, x1: i32, y1: i32, color: u32, buffer: &mut [u32]) {
+    let mut x0 = x0; let mut y0 = y0;
+    let dx = (x1 - x0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let dy = -(y1 - y0).abs();
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        plot(x0, y0, color, buffer);
+        if x0 == x1 && y0 == y1 { break; }
+        let e2 = 2 * err;
+        if e2 >= dy { err += dy; x0 += sx; }
+        if e2 <= dx { err += dx; y0 += sy; }
+    }
+}
+
+pub fn plot(x: i32, y: i32, color: u32, buffer: &mut [u32]) {
+    if x < 0 || y < 0 { return; }
+    let x = x as usize; let y = y as usize;
+    if x >= VIZ_W || y >= VIZ_H { return; }
+    buffer[y * VIZ_W + x] = color;
+}
+
+fn draw_grid(buf: &mut [u32], spacing: usize, color: u32) {
+    for x in (0..VIZ_W).step_by(spacing.max(1)) {
+        for y in 0..VIZ_H { buf[y * VIZ_W + x] = color; }
+    }
+    for y in (0..VIZ_H).step_by(spacing.max(1)) {
+        for x in 0..VIZ_W { buf[y * VIZ_W + x] = color; }
+    }
+}
+
+fn draw_axes(buf: &mut [u32], pad: usize, color: u32) {
+    let x0 = pad; let x1 = VIZ_W - pad;
+    let y0 = pad; let y1 = VIZ_H - pad;
+    for x in x0..=x1 { buf[y1 * VIZ_W + x] = color; }
+    for y in y0..=y1 { buf[y * VIZ_W + x0] = color; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAD: usize = 10;
+    const W: usize = VIZ_W - 2 * PAD;
+    const H: usize = VIZ_H - 2 * PAD;
+
+    #[test]
+    fn point_xy_spans_the_plot_area() {
+        // First point on the left edge, last on the right
+        assert_eq!(point_xy(0, 5, 100, 10, W, H, PAD).0, PAD);
+        assert_eq!(point_xy(99, 5, 100, 10, W, H, PAD).0, PAD + W - 1);
+        // Peak bit length at the top, zero at the bottom
+        assert_eq!(point_xy(0, 10, 100, 10, W, H, PAD).1, PAD);
+        assert_eq!(point_xy(0, 0, 100, 10, W, H, PAD).1, PAD + H - 1);
+    }
+
+    #[test]
+    fn point_xy_handles_degenerate_input() {
+        // A single point and a zero maximum must not divide by zero or leave the buffer
+        let (x, y) = point_xy(0, 0, 1, 0, W, H, PAD);
+        assert!(x < VIZ_W && y < VIZ_H);
+        let (x, y) = point_xy(1, 5000, 2, 5000, VIZ_W, VIZ_H, 0);
+        assert_eq!((x, y), (VIZ_W - 1, 0));
+    }
+
+    #[test]
+    fn history_unpins_when_the_viewed_start_is_evicted() {
+        let mut h = History::new();
+        let n = |i: usize| BigUint::from(i);
+        for i in 0..HISTORY_LEN { assert!(h.push(n(i), Outcome::ReachesOne)); }
+        assert!(!h.push(n(HISTORY_LEN - 1), Outcome::ReachesOne), "repeat of the newest");
+        // Pin start 1, then keep testing: the view follows it until it is evicted
+        for _ in 0..HISTORY_LEN - 3 { h.older(); }
+        assert_eq!(h.older().unwrap().0, n(1));
+        h.push(n(HISTORY_LEN), Outcome::ReachesOne);
+        assert_eq!(h.view.map(|i| &h.entries[i].0), Some(&n(1)));
+        h.push(n(HISTORY_LEN + 1), Outcome::ReachesOne);
+        assert_eq!(h.view, None, "start 1 is gone; back to live");
+        assert_eq!(h.newest().unwrap().0, n(HISTORY_LEN + 1));
+        // Stepping forward from the second newest returns to live as well
+        h.older();
+        assert_eq!(h.newer().unwrap().0, n(HISTORY_LEN + 1));
+        assert_eq!(h.view, None);
+    }
+
+    #[test]
+    fn point_xy_is_monotonic() {
+        let mut last = (0, usize::MAX);
+        for i in 0..50 {
+            let p = point_xy(i, i, 50, 49, W, H, PAD);
+            assert!(p.0 >= last.0 && p.1 <= last.1, "point {i}: {p:?} after {last:?}");
+            last = p;
+        }
+    }
+}
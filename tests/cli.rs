@@ -0,0 +1,125 @@
+// End-to-end checks through the command line, using the same known data as the unit tests
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn bin() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_rust_collatz_solution"))
+}
+
+fn run_with_stdin(args: &[&str], input: &str) -> String {
+    let mut child = bin().args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{args:?} failed");
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn classify_reports_golden_values_in_order() {
+    let out = run_with_stdin(&["classify", "--format", "csv", "--threads", "4"], "27\n97\n# comment\n871\n2^10\n");
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[0], "input,start,bits,outcome,total_steps,odd_steps,even_steps,glide,peak_step,peak_bits,peak,error");
+    let steps: Vec<(&str, &str)> = rows[1..].iter().map(|r| {
+        let f: Vec<&str> = r.split(',').collect();
+        (f[0], f[4])
+    }).collect();
+    assert_eq!(steps, [("27", "111"), ("97", "118"), ("871", "178"), ("2^10", "10")]);
+    assert!(rows[1].contains(",9232,"), "peak of 27: {}", rows[1]);
+}
+
+#[test]
+fn orbit_export_has_one_row_per_step() {
+    let out = bin().args(["orbit", "27"]).output().unwrap();
+    assert!(out.status.success());
+    let text = String::from_utf8(out.stdout).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    // header + steps 0..=111
+    assert_eq!(rows.len(), 1 + 112);
+    assert_eq!(rows[1], "0,27,5,1");
+    assert_eq!(*rows.last().unwrap(), "111,1,1,1");
+}
+
+#[test]
+fn verify_range_is_deterministic_and_checks_expectations() {
+    let run = |extra: &[&str]| {
+        bin().args(["verify-range", "--start", "1", "--count", "2000"]).args(extra).output().unwrap()
+    };
+    let first = run(&[]);
+    assert!(first.status.success());
+    let line = String::from_utf8(first.stdout).unwrap();
+    assert!(line.contains("processed=2000 reaches_one=2000"), "{line}");
+    let digest = line.split("digest=").nth(1).unwrap().trim().to_string();
+    assert!(run(&["--expect", &digest]).status.success());
+    assert!(!run(&["--expect", "0000000000000000"]).status.success());
+}
+
+#[test]
+fn sequential_run_finishes_and_writes_checkpoint() {
+    let dir = std::env::temp_dir().join(format!("collatz-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let checkpoint = dir.join("collatz-checkpoint.txt");
+    let out = bin()
+        .args(["--no-viz", "--no-random", "--start", "1", "--count", "1000", "--checkpoint"])
+        .arg(&checkpoint)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("outcomes: reaches_one=1000 nontrivial_cycle=0 steps_overflow=0"), "{stderr}");
+    assert_eq!(std::fs::read_to_string(&checkpoint).unwrap().trim(), "1001");
+    // The default lands in the working directory, and --resume takes any expression
+    let out = bin().current_dir(&dir).args(["--no-viz", "--no-random", "--start", "1", "--count", "10"]).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(std::fs::read_to_string(&checkpoint).unwrap().trim(), "11");
+    std::fs::write(&checkpoint, "2^10\n").unwrap();
+    let out = bin().current_dir(&dir).args(["--no-viz", "--no-random", "--resume", "--count", "10"]).output().unwrap();
+    assert!(String::from_utf8(out.stderr).unwrap().contains("Resuming from checkpoint collatz-checkpoint.txt: 1024"));
+    assert_eq!(std::fs::read_to_string(&checkpoint).unwrap().trim(), "1034");
+    std::fs::write(&checkpoint, "12x\n").unwrap();
+    let out = bin().current_dir(&dir).args(["--no-viz", "--no-random", "--resume", "--count", "10"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr).unwrap().contains("collatz-checkpoint.txt: "));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bad_numbers_are_rejected_with_a_message() {
+    let out = bin().args(["--no-viz", "--start", "2^3-9"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr).unwrap().contains("--start: negative result"));
+    // every numeric flag goes through the same parser and names itself on error
+    let cases: &[&[&str]] = &[
+        &["--no-viz", "--viz-interval", "ten"],
+        &["--no-viz", "--viz-max-steps", "1e"],
+        &["--no-viz", "--progress-interval", "-5"],
+        &["--no-viz", "--strata-bits", "x"],
+        &["coordinator", "--lease-secs", "soon"],
+        &["coordinator", "--verify-every", "2^"],
+        &["status", "--gaps", "many"],
+        &["classify", "--threads", "all"],
+    ];
+    for args in cases {
+        let out = bin().args(*args).output().unwrap();
+        assert!(!out.status.success(), "{args:?}");
+        let flag = args[args.len() - 2];
+        assert!(String::from_utf8(out.stderr).unwrap().contains(&format!("{flag}: ")), "{args:?}");
+    }
+    let out = bin().args(["--no-viz", "--strata-bits", "21"]).output().unwrap();
+    assert!(String::from_utf8(out.stderr).unwrap().contains("--strata-bits: must be 1 to 20"));
+}
+
+#[test]
+fn start_zero_is_rejected() {
+    // 0 maps to itself, so a scan from it used to spin forever
+    for extra in [&[][..], &["--no-batch"][..]] {
+        let out = bin().args(["--no-viz", "--no-random", "--start", "0", "--count", "2"]).args(extra).output().unwrap();
+        assert!(!out.status.success());
+        assert!(String::from_utf8(out.stderr).unwrap().contains("0 has no Collatz orbit"));
+    }
+    for sub in ["verify-range", "coordinator"] {
+        let out = bin().args([sub, "--start", "0", "--count", "2"]).output().unwrap();
+        assert!(!out.status.success(), "{sub}");
+        assert!(String::from_utf8(out.stderr).unwrap().contains("0 has no Collatz orbit"), "{sub}");
+    }
+}